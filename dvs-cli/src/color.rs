@@ -0,0 +1,100 @@
+use clap::ValueEnum;
+use dvs::{FileStatus, Status};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color only when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `no_color` is whether the `NO_COLOR` environment variable is set to a non-empty value
+    pub fn enabled(self, no_color: bool, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal && !no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Whether `NO_COLOR` is set, see https://no-color.org
+pub fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+fn status_color(status: Status) -> &'static str {
+    match status {
+        Status::Current => "\x1b[32m",
+        Status::Absent => "\x1b[31m",
+        Status::Unsynced => "\x1b[33m",
+        Status::Unreadable => "\x1b[35m",
+        Status::Untracked => "",
+    }
+}
+
+/// The `path: status` line printed by `dvs status`.
+/// Empty files all share the same object so we point them out.
+pub fn format_status_line(file_status: &FileStatus, color: bool) -> String {
+    let status = file_status.status;
+    let code = status_color(status);
+    let mut line = if color && !code.is_empty() {
+        format!("{}: {code}{status:?}\x1b[0m", file_status.path.display())
+    } else {
+        format!("{}: {status:?}", file_status.path.display())
+    };
+    if file_status.size == Some(0) {
+        line.push_str(" (empty)");
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file_status(status: Status, size: u64) -> FileStatus {
+        FileStatus {
+            path: PathBuf::from("data.csv"),
+            status,
+            size: Some(size),
+            mtime: None,
+            annotations: Default::default(),
+        }
+    }
+
+    #[test]
+    fn auto_is_plain_outside_a_terminal_or_with_no_color() {
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(ColorChoice::Auto.enabled(false, true));
+        assert!(ColorChoice::Always.enabled(true, false));
+        assert!(!ColorChoice::Never.enabled(false, true));
+
+        let line = format_status_line(
+            &file_status(Status::Absent, 3),
+            ColorChoice::Auto.enabled(false, false),
+        );
+        assert_eq!(line, "data.csv: Absent");
+        assert!(!line.contains('\x1b'));
+    }
+
+    #[test]
+    fn always_includes_ansi_codes() {
+        let line = format_status_line(
+            &file_status(Status::Current, 3),
+            ColorChoice::Always.enabled(true, false),
+        );
+        assert_eq!(line, "data.csv: \x1b[32mCurrent\x1b[0m");
+    }
+
+    #[test]
+    fn empty_files_are_pointed_out() {
+        let line = format_status_line(&file_status(Status::Current, 0), false);
+        assert_eq!(line, "data.csv: Current (empty)");
+    }
+}
@@ -0,0 +1,58 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{Result, bail};
+
+/// Asks the user to confirm adding `count` files if it is above `threshold`.
+/// Without a terminal to ask on, it errors unless `yes` is set.
+pub fn confirm_large_add(
+    count: usize,
+    threshold: usize,
+    yes: bool,
+    is_terminal: bool,
+    mut input: impl BufRead,
+) -> Result<()> {
+    if yes || count <= threshold {
+        return Ok(());
+    }
+    if !is_terminal {
+        bail!(
+            "Refusing to add {count} files (more than {threshold}) without confirmation, \
+             use --yes to proceed"
+        );
+    }
+
+    eprint!("About to add {count} files, continue? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        bail!("Aborted, no files were added")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn below_threshold_never_asks() {
+        assert!(confirm_large_add(10, 1000, false, false, &b""[..]).is_ok());
+        assert!(confirm_large_add(1000, 1000, false, false, &b""[..]).is_ok());
+    }
+
+    #[test]
+    fn above_threshold_without_terminal_requires_yes() {
+        let err = confirm_large_add(1001, 1000, false, false, &b""[..]).unwrap_err();
+        assert!(err.to_string().contains("--yes"));
+        assert!(confirm_large_add(1001, 1000, true, false, &b""[..]).is_ok());
+    }
+
+    #[test]
+    fn above_threshold_on_terminal_asks() {
+        assert!(confirm_large_add(1001, 1000, false, true, &b"y\n"[..]).is_ok());
+        assert!(confirm_large_add(1001, 1000, false, true, &b"n\n"[..]).is_err());
+        assert!(confirm_large_add(1001, 1000, false, true, &b""[..]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Exit codes of the CLI. They are part of its interface: scripts can rely on them
+/// and they should not change.
+///
+/// Errors are mapped to `Failure` unless they were created with `ExitStatus::error`,
+/// or are a `dvs::IntegrityError`.
+/// Invalid arguments are reported by clap, which uses `Usage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    /// Any error without a more specific category
+    Failure = 1,
+    /// Invalid arguments or nothing to act on
+    Usage = 2,
+    /// Not run from within a dvs repository
+    NotARepo = 3,
+    /// Stored data is missing or doesn't match its metadata
+    Integrity = 4,
+    /// Some files were processed but others failed, eg `add --keep-going`
+    Partial = 5,
+}
+
+/// An error ending the process with a given exit status
+#[derive(Debug)]
+struct StatusError {
+    status: ExitStatus,
+    message: String,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        StatusError {
+            status: self,
+            message: message.into(),
+        }
+        .into()
+    }
+
+    /// The exit status the process should end with for that error
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(e) = err.chain().find_map(|e| e.downcast_ref::<StatusError>()) {
+            return e.status;
+        }
+        if err.chain().any(|e| e.is::<dvs::IntegrityError>()) {
+            return ExitStatus::Integrity;
+        }
+        ExitStatus::Failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn errors_map_to_their_exit_code() {
+        let err = ExitStatus::NotARepo.error("Not in a DVS repository");
+        assert_eq!(err.to_string(), "Not in a DVS repository");
+        assert_eq!(ExitStatus::of(&err).code(), 3);
+
+        let err = Err::<(), _>(ExitStatus::Integrity.error("1 files failed verification"))
+            .context("while getting")
+            .unwrap_err();
+        assert_eq!(ExitStatus::of(&err), ExitStatus::Integrity);
+
+        let err = Err::<(), _>(anyhow::Error::new(dvs::IntegrityError::new("corrupted")))
+            .context("Failed to get data.csv")
+            .unwrap_err();
+        assert_eq!(ExitStatus::of(&err), ExitStatus::Integrity);
+
+        assert_eq!(ExitStatus::of(&anyhow!("oops")), ExitStatus::Failure);
+    }
+}
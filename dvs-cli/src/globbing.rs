@@ -3,8 +3,8 @@ use std::ffi::OsStr;
 use std::path::PathBuf;
 
 use anyhow::{Result, anyhow, bail};
-use dvs::paths::DvsPaths;
-use globset::{GlobBuilder, GlobMatcher};
+use dvs::paths::{DvsPaths, special_file_kind};
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
 use walkdir::WalkDir;
 
 /// Builds the glob matching the rg behaviour
@@ -21,6 +21,15 @@ fn build_glob_matcher(pattern: Option<&str>) -> Result<Option<GlobMatcher>> {
         .transpose()
 }
 
+/// Builds a set out of all the patterns, with the same matching rules as `build_glob_matcher`
+pub fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    Ok(builder.build()?)
+}
+
 /// Resolve paths for `add` command following ripgrep-style behavior:
 /// - Explicit files: added directly (glob ignored)
 /// - Explicit directories: walked and filtered by glob
@@ -61,7 +70,7 @@ pub fn resolve_paths_for_add(
             if let Some(matcher) = &glob_matcher {
                 for entry in WalkDir::new(&full_path).into_iter().filter_map(|e| e.ok()) {
                     let entry_path = entry.path().canonicalize()?;
-                    // Skip directories and metadata root folder
+                    // Skip directories, special files like named pipes and metadata root folder
                     if !entry_path.is_file() || entry_path.starts_with(&metadata_root) {
                         continue;
                     }
@@ -81,6 +90,11 @@ pub fn resolve_paths_for_add(
                     }
                 }
             }
+        } else if let Some(kind) = special_file_kind(&full_path) {
+            bail!(
+                "{} is a {kind}, only regular files can be added",
+                path.display()
+            );
         } else {
             bail!("Path is not a file or directory: {}", path.display());
         }
@@ -283,4 +297,27 @@ mod tests {
         assert!(result.contains(&PathBuf::from("data/a.csv")));
         assert!(result.contains(&PathBuf::from("data/subdir/c.csv")));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_through_symlinked_repo_root() {
+        let (temp, _) = setup_test_repo();
+        let other = TempDir::new().unwrap();
+        let link = other.path().join("link");
+        std::os::unix::fs::symlink(temp.path(), &link).unwrap();
+        let dvs_paths = DvsPaths::new(link.join("data"), link.clone(), ".dvs");
+
+        let result = resolve_paths_for_add(vec![PathBuf::from("a.csv")], None, &dvs_paths).unwrap();
+        assert_eq!(result, HashSet::from([PathBuf::from("data/a.csv")]));
+
+        let result =
+            resolve_paths_for_add(vec![PathBuf::from(".")], Some("**/*.csv"), &dvs_paths).unwrap();
+        assert_eq!(
+            result,
+            HashSet::from([
+                PathBuf::from("data/a.csv"),
+                PathBuf::from("data/subdir/c.csv")
+            ])
+        );
+    }
 }
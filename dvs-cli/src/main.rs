@@ -1,17 +1,31 @@
+mod color;
+mod confirm;
+mod exit;
 mod globbing;
+mod output;
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
 use clap::{Parser, Subcommand};
 use serde_json::json;
 
-use crate::globbing::{resolve_paths_for_add, resolve_paths_for_get};
-use dvs::Compression;
+use crate::color::{ColorChoice, format_status_line, no_color_env};
+use crate::confirm::confirm_large_add;
+use crate::exit::ExitStatus;
+use crate::globbing::{build_glob_set, resolve_paths_for_add, resolve_paths_for_get};
+use crate::output::print_json;
 use dvs::config::Config;
-use dvs::file::{Outcome, add_files, get_files, get_status};
+use dvs::file::{
+    Outcome, VerifyOutcome, add_files, find_case_collisions, get_files, get_status, verify_files,
+};
 use dvs::init::init;
 use dvs::paths::DvsPaths;
+use dvs::{
+    AddOptions, Compression, GetOptions, HashOptions, HashProgress, StatusOptions, StatusTotals,
+};
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
@@ -33,6 +47,10 @@ pub enum Command {
         /// Disable compression of stored files. Compression defaults to zstd
         #[clap(long)]
         no_compression: bool,
+        /// Initialize even when inside another dvs repository or when the storage
+        /// directory is inside the repository
+        #[clap(long)]
+        force: bool,
     },
     /// Adds the given files to dvs. You can use a glob or paths.
     /// If you pass a directory and a glob, the glob will be ran from that directory
@@ -43,9 +61,53 @@ pub enum Command {
         glob: Option<String>,
         #[clap(long)]
         message: Option<String>,
+        /// Attach a `key=value` annotation to the added files, replacing the value a key
+        /// already had. Can be repeated
+        #[clap(long = "annotate", value_name = "KEY=VALUE", value_parser = parse_annotation)]
+        annotations: Vec<(String, String)>,
+        /// Unix permissions for the stored files of this add (octal, e.g., "770"),
+        /// overriding the ones set at init
+        #[clap(long)]
+        permissions: Option<String>,
+        /// Unix group for the stored files of this add, overriding the one set at init
+        #[clap(long)]
+        group: Option<String>,
+        /// Add zero-byte files instead of refusing them.
+        /// They are usually the output of a failed generation step
+        #[clap(long)]
+        allow_empty: bool,
+        /// Do not ask for confirmation when adding more files than the
+        /// `add_confirmation_threshold` of the config (1000 by default)
+        #[clap(long, short)]
+        yes: bool,
+        /// Keep adding the other files when one fails, reporting the failures at the end
+        #[clap(long, short)]
+        keep_going: bool,
+        /// Only track files that are not tracked yet, skipping the others even if they changed
+        #[clap(long)]
+        if_absent: bool,
+        /// Hash files using all cores, faster for multi-gigabyte files on fast storage
+        #[clap(long)]
+        parallel_hashing: bool,
+    },
+    /// Gets the status of each files in the current repository.
+    /// Only tracked files are listed: a tracked file missing from the working tree
+    /// is reported as absent, untracked files are not shown
+    Status {
+        /// List the files recorded in the metadata folder, so a tracked file deleted locally
+        /// is reported as absent. This is the default: files are never discovered
+        /// from the working tree
+        #[clap(long)]
+        tracked_only: bool,
+        /// Skip tracked files matching this glob, relative to the repository root.
+        /// Can be repeated
+        #[clap(long)]
+        exclude: Vec<String>,
+        /// Color the status of each file. `auto` only colors when writing to a terminal
+        /// and `NO_COLOR` is not set
+        #[clap(long, value_enum, default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
     },
-    /// Gets the status of each files in the current repository
-    Status,
     /// Retrieves the given files from dvs storage. You can use a glob or paths.
     /// If you pass a directory and a glob, the glob will be ran from that directory
     Get {
@@ -53,15 +115,56 @@ pub enum Command {
         paths: Vec<PathBuf>,
         #[clap(long, short)]
         glob: Option<String>,
+        /// Only check that the stored files are present and not corrupted,
+        /// without writing them to the working tree
+        #[clap(long)]
+        verify_only: bool,
+        /// Replace local files that have been modified since they were added
+        #[clap(long, conflicts_with = "no_overwrite")]
+        overwrite: bool,
+        /// Refuse to replace local files that have been modified since they were added.
+        /// This is the default
+        #[clap(long)]
+        no_overwrite: bool,
+        /// Retrieve all the files or none: nothing is written to the working tree
+        /// unless every file was retrieved and verified
+        #[clap(long)]
+        atomic: bool,
     },
 }
 
+/// Files smaller than this are hashed without printing progress
+const HASH_PROGRESS_MIN_SIZE: u64 = 100 * 1024 * 1024;
+
+fn print_hash_progress(path: &Path, done: u64, total: u64) {
+    let percent = done * 100 / total.max(1);
+    eprint!("\rHashing {}: {percent}%", path.display());
+    if done >= total {
+        eprintln!();
+    }
+    let _ = std::io::stderr().flush();
+}
+
+/// Parses a `key=value` annotation given on the command line
+fn parse_annotation(s: &str) -> Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid annotation '{s}': expected KEY=VALUE"))?;
+    if key.is_empty() {
+        return Err(anyhow!("Invalid annotation '{s}': key cannot be empty"));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
 #[derive(Parser)]
 #[clap(version, author, about, subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// Output results as JSON
+    /// Output results as JSON, on a single line
     #[clap(long, global = true)]
     pub json: bool,
+    /// Output results as indented JSON, easier to read when debugging
+    #[clap(long, global = true)]
+    pub json_pretty: bool,
 
     #[clap(subcommand)]
     pub command: Command,
@@ -70,8 +173,15 @@ pub struct Cli {
 fn try_main() -> Result<()> {
     env_logger::init();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    cli.json |= cli.json_pretty;
     let current_dir = std::env::current_dir()?;
+    // Progress is only for humans, keep JSON output clean
+    let hash_progress = HashProgress {
+        min_size: HASH_PROGRESS_MIN_SIZE,
+        callback: &print_hash_progress,
+    };
+    let hash_progress = (!cli.json).then_some(&hash_progress);
 
     match cli.command {
         Command::Init {
@@ -80,6 +190,7 @@ fn try_main() -> Result<()> {
             permissions,
             group,
             no_compression,
+            force,
         } => {
             let mut config = Config::new_local(path, permissions, group)?;
             if no_compression {
@@ -88,9 +199,9 @@ fn try_main() -> Result<()> {
             if let Some(m) = metadata_folder_name {
                 config.set_metadata_folder_name(m);
             }
-            init(&current_dir, config)?;
+            init(&current_dir, config, force)?;
             if cli.json {
-                println!("{}", json!({"status": "initialized"}));
+                print_json(json!({"status": "initialized"}), cli.json_pretty);
             } else {
                 println!("DVS Initialized");
             }
@@ -99,67 +210,177 @@ fn try_main() -> Result<()> {
             paths,
             glob,
             message,
+            annotations,
+            permissions,
+            group,
+            allow_empty,
+            yes,
+            keep_going,
+            if_absent,
+            parallel_hashing,
         } => {
-            let config =
-                Config::find(&current_dir).ok_or_else(|| anyhow!("Not in a DVS repository"))??;
+            let mut config = find_config(&current_dir)?;
+            config.override_object_permissions(permissions, group)?;
             let dvs_paths = DvsPaths::from_cwd(&config)?;
             let all_paths: Vec<_> = resolve_paths_for_add(paths, glob.as_deref(), &dvs_paths)?
                 .into_iter()
                 .collect();
             if all_paths.is_empty() {
-                return Err(anyhow!("No files to add"));
+                return Err(ExitStatus::Usage.error("No files to add"));
             }
+            confirm_large_add(
+                all_paths.len(),
+                config.add_confirmation_threshold(),
+                yes,
+                std::io::stdin().is_terminal() && std::io::stderr().is_terminal(),
+                std::io::stdin().lock(),
+            )?;
 
-            let results = add_files(
-                all_paths,
-                &dvs_paths,
-                config.backend(),
+            let options = AddOptions {
                 message,
-                config.compression(),
-            )?;
+                annotations: BTreeMap::from_iter(annotations),
+                compression: config.compression(),
+                compression_level: config.compression_level(),
+                allow_empty,
+                keep_going,
+                if_absent,
+                text_extensions: config.text_extensions(),
+                validate_command: config.validate_command().map(|c| c.to_vec()),
+                progress: hash_progress,
+                hashing: HashOptions {
+                    parallel: parallel_hashing,
+                    ..Default::default()
+                },
+            };
+            let results = add_files(all_paths, &dvs_paths, config.backend(), &options)?;
             if cli.json {
-                println!("{}", serde_json::to_string(&results)?);
+                print_json(json!({ "files": results }), cli.json_pretty);
             } else {
-                for result in results {
-                    println!("Added: {}", result.path.display());
+                for result in &results {
+                    for warning in &result.warnings {
+                        eprintln!("Warning: {warning}");
+                    }
+                    match (&result.error, result.outcome) {
+                        (Some(e), _) => println!("Failed: {}: {e}", result.path.display()),
+                        (None, Some(Outcome::Skipped)) => {
+                            println!("Skipped, already tracked: {}", result.path.display())
+                        }
+                        (None, _) => println!("Added: {}", result.path.display()),
+                    }
                 }
             }
+            let failed = results.iter().filter(|r| r.error.is_some()).count();
+            if failed > 0 {
+                return Err(ExitStatus::Partial.error(format!("{failed} files failed to be added")));
+            }
         }
-        Command::Status => {
-            let config =
-                Config::find(&current_dir).ok_or_else(|| anyhow!("Not in a DVS repository"))??;
+        Command::Status {
+            tracked_only: _,
+            exclude,
+            color,
+        } => {
+            let config = find_config(&current_dir)?;
             let paths = DvsPaths::from_cwd(&config)?;
 
-            let statuses = get_status(&paths)?;
+            let options = StatusOptions {
+                exclude: build_glob_set(&exclude)?,
+                progress: hash_progress,
+            };
+            let statuses = get_status(&paths, &options)?;
+            let totals = StatusTotals::from_statuses(&statuses);
+            let case_collisions = find_case_collisions(statuses.iter().map(|s| s.path.as_path()));
             if cli.json {
-                println!("{}", serde_json::to_string(&statuses)?);
+                print_json(
+                    json!({"files": statuses, "totals": totals, "case_collisions": case_collisions}),
+                    cli.json_pretty,
+                );
             } else if statuses.is_empty() {
                 println!("No tracked files");
             } else {
+                let color = color.enabled(no_color_env(), std::io::stdout().is_terminal());
                 for file_status in statuses {
-                    println!("{}: {:?}", file_status.path.display(), file_status.status);
+                    println!("{}", format_status_line(&file_status, color));
+                    for (key, value) in &file_status.annotations {
+                        println!("    {key}={value}");
+                    }
+                }
+                println!(
+                    "\n{} tracked files, {} bytes: {} bytes current, {} bytes absent",
+                    totals.files, totals.bytes, totals.current_bytes, totals.absent_bytes
+                );
+                if !case_collisions.is_empty() {
+                    println!(
+                        "\nThe following tracked paths only differ by case and will overwrite \
+                         each other on case-insensitive filesystems:"
+                    );
+                    for group in &case_collisions {
+                        let group: Vec<_> = group.iter().map(|p| p.display().to_string()).collect();
+                        println!("    {}", group.join(", "));
+                    }
                 }
             }
         }
-        Command::Get { paths, glob } => {
-            let config =
-                Config::find(&current_dir).ok_or_else(|| anyhow!("Not in a DVS repository"))??;
+        Command::Get {
+            paths,
+            glob,
+            verify_only,
+            overwrite,
+            no_overwrite: _,
+            atomic,
+        } => {
+            let config = find_config(&current_dir)?;
             let dvs_paths = DvsPaths::from_cwd(&config)?;
             let all_paths: Vec<_> = resolve_paths_for_get(paths, glob.as_deref(), &dvs_paths)?
                 .into_iter()
                 .collect();
             if all_paths.is_empty() {
-                return Err(anyhow!("No files to get"));
+                return Err(ExitStatus::Usage.error("No files to get"));
             }
 
-            let results = get_files(all_paths, &dvs_paths, config.backend())?;
+            if verify_only {
+                let results = verify_files(all_paths, &dvs_paths, config.backend())?;
+                if cli.json {
+                    print_json(json!({ "files": results }), cli.json_pretty);
+                } else {
+                    for result in &results {
+                        match result.outcome {
+                            VerifyOutcome::Ok => println!("OK: {}", result.path.display()),
+                            VerifyOutcome::Corrupt => {
+                                println!("Corrupt: {}", result.path.display())
+                            }
+                            VerifyOutcome::Missing => {
+                                println!("Missing: {}", result.path.display())
+                            }
+                        }
+                    }
+                }
+                let failed = results
+                    .iter()
+                    .filter(|r| r.outcome != VerifyOutcome::Ok)
+                    .count();
+                if failed > 0 {
+                    return Err(
+                        ExitStatus::Integrity.error(format!("{failed} files failed verification"))
+                    );
+                }
+                return Ok(());
+            }
+
+            let results = get_files(
+                all_paths,
+                &dvs_paths,
+                config.backend(),
+                &GetOptions { overwrite, atomic },
+            )?;
             if cli.json {
-                println!("{}", serde_json::to_string(&results)?);
+                print_json(json!({ "files": results }), cli.json_pretty);
             } else {
                 for result in results {
                     match result.outcome {
                         Outcome::Copied => println!("Retrieved: {}", result.path.display()),
-                        Outcome::Present => println!("Up to date: {}", result.path.display()),
+                        Outcome::Present | Outcome::Skipped => {
+                            println!("Up to date: {}", result.path.display())
+                        }
                     }
                 }
             }
@@ -168,9 +389,35 @@ fn try_main() -> Result<()> {
     Ok(())
 }
 
+fn find_config(current_dir: &Path) -> Result<Config> {
+    Config::find(current_dir)
+        .ok_or_else(|| ExitStatus::NotARepo.error("Not in a DVS repository"))?
+}
+
 fn main() {
-    if let Err(e) = try_main() {
-        eprintln!("{e:?}");
-        ::std::process::exit(1)
+    let status = match try_main() {
+        Ok(()) => ExitStatus::Success,
+        Err(e) => {
+            eprintln!("{e:?}");
+            ExitStatus::of(&e)
+        }
+    };
+    ::std::process::exit(status.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_accepts_tracked_only() {
+        let cli = Cli::try_parse_from(["dvs", "status", "--tracked-only"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Status {
+                tracked_only: true,
+                ..
+            }
+        ));
     }
 }
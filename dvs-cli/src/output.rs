@@ -0,0 +1,65 @@
+//! JSON output of the CLI.
+//!
+//! Every command prints a single JSON object when `--json` is passed, on one line unless
+//! `--json-pretty` is used instead. It always contains
+//! `schema_version`. It is bumped whenever a field is removed, renamed or changes meaning,
+//! so tools can detect formats they don't know about.
+//!
+//! - `init`: `{"status": "initialized"}`
+//! - `add`: `{"files": [{"path", "outcome"}]}`, the outcome being `copied`, `present`
+//!   or `skipped` with `--if-absent`.
+//!   With `--keep-going`, files that failed have an `error` instead of an outcome.
+//!   Files can also have `warnings`, eg a `.csv` that looks compressed
+//! - `status`: `{"files": [...], "totals": {...}, "case_collisions": [[...]]}`
+//! - `get`: `{"files": [{"path", "outcome"}]}`, the outcome being `copied` or `present`,
+//!   or `ok`, `corrupt` or `missing` with `--verify-only`
+use serde_json::Value;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Adds the `schema_version` to a JSON object
+pub fn versioned(mut output: Value) -> Value {
+    let object = output
+        .as_object_mut()
+        .expect("JSON output should always be an object");
+    object.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    output
+}
+
+pub fn format_json(output: Value, pretty: bool) -> String {
+    let output = versioned(output);
+    if pretty {
+        serde_json::to_string_pretty(&output).expect("valid json")
+    } else {
+        output.to_string()
+    }
+}
+
+pub fn print_json(output: Value, pretty: bool) {
+    println!("{}", format_json(output, pretty));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn output_includes_schema_version() {
+        let output = versioned(json!({"files": []}));
+        assert_eq!(output["schema_version"], SCHEMA_VERSION);
+        assert_eq!(output["files"], json!([]));
+    }
+
+    #[test]
+    fn pretty_output_is_indented_and_compact_is_one_line() {
+        let output = json!({"files": [{"path": "a.csv"}]});
+        let compact = format_json(output.clone(), false);
+        assert!(!compact.contains('\n'));
+
+        let pretty = format_json(output, true);
+        assert!(pretty.contains("\n  \"files\": ["));
+        let parsed: Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(parsed, serde_json::from_str::<Value>(&compact).unwrap());
+    }
+}
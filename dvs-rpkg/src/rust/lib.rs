@@ -15,7 +15,7 @@ use anyhow::{anyhow, Result};
 use dvs::config::Config;
 use dvs::init::init;
 use dvs::paths::DvsPaths;
-use dvs::{add_files, get_files, get_status, AddResult, FileStatus, GetResult};
+use dvs::{add_files, get_files, get_status, AddResult, FileStatus, GetResult, StatusOptions};
 
 #[miniextendr]
 pub fn dvs_init(
@@ -29,7 +29,7 @@ pub fn dvs_init(
     if let Some(m) = metadata_folder_name {
         config.set_metadata_folder_name(m);
     }
-    init(&directory, config)?;
+    init(&directory, config, false)?;
 
     r_println!("DVS Initialized");
     Ok(list!("status" = "initialized"))
@@ -64,7 +64,7 @@ pub fn dvs_status() -> Result<DataFrame<AsSerializeRow<FileStatus>>> {
     let config = Config::find(&current_dir).ok_or_else(|| anyhow!("Not in a DVS repository"))??;
     let paths = DvsPaths::from_cwd(&config)?;
 
-    let statuses = get_status(&paths)?;
+    let statuses = get_status(&paths, &StatusOptions::default())?;
 
     Ok(DataFrame::from_iter(statuses.into_iter().map(|x| x.into())))
 }
@@ -1,25 +1,45 @@
 use std::collections::HashSet;
 use std::io::BufRead;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::Hashes;
+use crate::file::Outcome;
+use crate::paths::to_portable_path;
 use anyhow::Result;
 use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// The audit log is shared by every dvs version writing to the storage, so it only ever
+/// changes by adding fields that are optional or actions. Older versions read entries from
+/// newer ones as best they can instead of failing on them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Action {
     Add,
+    /// A file was retrieved from storage, or checked as already present
+    Get,
+    /// An action recorded by a newer version of dvs
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditFile {
+    /// Always uses `/` as separator, see `to_portable_path`
     pub path: PathBuf,
     pub hashes: Hashes,
 }
 
+impl AuditFile {
+    pub fn new(relative_path: &Path, hashes: Hashes) -> Self {
+        Self {
+            path: to_portable_path(relative_path),
+            hashes,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEntry {
     pub operation_id: String,
@@ -27,10 +47,13 @@ pub struct AuditEntry {
     pub user: String,
     pub file: AuditFile,
     pub action: Action,
+    /// Only recorded for gets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
 }
 
 impl AuditEntry {
-    pub fn new_add(operation_id: Uuid, file: AuditFile) -> Self {
+    fn new(operation_id: Uuid, file: AuditFile, action: Action, outcome: Option<Outcome>) -> Self {
         let timestamp = Timestamp::now().as_second();
         let user = whoami::username().unwrap_or_else(|_| "unknown".to_string());
 
@@ -39,26 +62,43 @@ impl AuditEntry {
             timestamp,
             user,
             file,
-            action: Action::Add,
+            action,
+            outcome,
         }
     }
+
+    pub fn new_add(operation_id: Uuid, file: AuditFile) -> Self {
+        Self::new(operation_id, file, Action::Add, None)
+    }
+
+    pub fn new_get(operation_id: Uuid, file: AuditFile, outcome: Outcome) -> Self {
+        Self::new(operation_id, file, Action::Get, Some(outcome))
+    }
 }
 
+/// Parses the JSON lines of the audit log, keeping the entries about `only_files` if not empty.
+/// Lines that can't be parsed, eg written by a newer version in a way we can't read or cut
+/// short by a crash, are skipped with a warning rather than making the whole log unreadable.
 pub fn parse_audit_log(
     reader: impl BufRead,
     only_files: &HashSet<PathBuf>,
 ) -> Result<Vec<AuditEntry>> {
-    reader
-        .lines()
-        .map(|line| line.map_err(anyhow::Error::from))
-        .filter_map(|line| match line {
-            Ok(l) if l.trim().is_empty() => None,
-            other => Some(other),
-        })
-        .map(|line| Ok(serde_json::from_str::<AuditEntry>(&line?)?))
-        .filter(|entry| match entry {
-            Ok(e) => only_files.is_empty() || only_files.contains(&e.file.path),
-            Err(_) => true, // propagate errors
-        })
-        .collect()
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = match serde_json::from_str::<AuditEntry>(&line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Skipping unreadable audit log line {}: {e}", i + 1);
+                continue;
+            }
+        };
+        if only_files.is_empty() || only_files.contains(&entry.file.path) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
 }
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 use std::fs::OpenOptions;
-use std::io::{BufReader, Write};
+use std::io::{self, BufReader, Write};
 use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow, bail};
@@ -8,11 +8,16 @@ use fs_err as fs;
 use serde::{Deserialize, Serialize};
 
 use crate::audit::{AuditEntry, parse_audit_log};
-use crate::backends::Backend;
+use crate::backends::{Backend, ObjectStat};
 use crate::config::Compression;
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, EncryptionKey};
+use crate::paths::to_portable_path;
 use crate::{HashAlg, Hashes};
 
 const AUDIT_LOG_FILENAME: &str = "audit.log.jsonl";
+/// Folder mapping the hash of each recorded algorithm to the full set of hashes
+const INDEX_FOLDER_NAME: &str = "index";
 
 /// Parse a permission string as an octal mode.
 /// Returns the mode as an u32.
@@ -47,12 +52,50 @@ fn resolve_group(_: &str) -> Result<()> {
     Ok(())
 }
 
+/// Apply the given permissions and group to a path.
+/// No-op on non-Unix or if neither permissions nor group are set.
+#[cfg(unix)]
+fn apply_perms_to(path: &Path, permissions: Option<&str>, group: Option<&str>) -> Result<()> {
+    use nix::unistd::chown;
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(perms) = permissions {
+        log::debug!("Setting permissions {} on {}", perms, path.display());
+        let mode = parse_permissions(perms)?;
+        let permissions = std::fs::Permissions::from_mode(mode);
+        fs::set_permissions(path, permissions)?;
+    }
+
+    if let Some(group_name) = group {
+        log::debug!("Setting group {} on {}", group_name, path.display());
+        let gid = resolve_group(group_name)?;
+        chown(path, None, Some(gid))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply_perms_to(_path: &Path, _permissions: Option<&str>, _group: Option<&str>) -> Result<()> {
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct LocalBackend {
     pub path: PathBuf,
     permissions: Option<String>,
     group: Option<String>,
     hash_alg: HashAlg,
+    /// Overrides for the stored objects only, set per invocation and never saved.
+    /// Directories keep the configured permissions since they are shared.
+    #[serde(skip)]
+    object_permissions: Option<String>,
+    #[serde(skip)]
+    object_group: Option<String>,
+    /// Read from the environment variable named in the config, never saved
+    #[cfg(feature = "encryption")]
+    #[serde(skip)]
+    encryption_key: Option<EncryptionKey>,
 }
 
 impl LocalBackend {
@@ -74,47 +117,252 @@ impl LocalBackend {
             permissions,
             group,
             hash_alg: HashAlg::Blake3,
+            object_permissions: None,
+            object_group: None,
+            #[cfg(feature = "encryption")]
+            encryption_key: None,
         })
     }
 
-    /// Apply configured permissions and group to a path.
-    /// No-op on non-Unix or if neither permissions nor group are set.
-    #[cfg(unix)]
-    pub fn apply_perms(&self, path: impl AsRef<Path>) -> Result<()> {
-        use nix::unistd::chown;
-        use std::os::unix::fs::PermissionsExt;
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Use different permissions and/or group than the configured ones for the objects
+    /// stored from now on. `None` keeps the configured value.
+    pub fn override_object_permissions(
+        &mut self,
+        permissions: Option<String>,
+        group: Option<String>,
+    ) -> Result<()> {
+        if let Some(ref perms) = permissions {
+            parse_permissions(perms)?;
+        }
+        if let Some(ref grp) = group {
+            resolve_group(grp)?;
+        }
+        self.object_permissions = permissions;
+        self.object_group = group;
+        Ok(())
+    }
 
-        let path = path.as_ref();
+    /// Encrypt the objects stored from now on with `key`, given as 64 hex characters,
+    /// and decrypt the encrypted ones when reading them
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&mut self, key: &str) -> Result<()> {
+        self.encryption_key = Some(EncryptionKey::from_hex(key)?);
+        Ok(())
+    }
 
-        if let Some(perms) = &self.permissions {
-            log::debug!("Setting permissions {} on {}", perms, path.display());
-            let mode = parse_permissions(perms)?;
-            let permissions = std::fs::Permissions::from_mode(mode);
-            fs::set_permissions(path, permissions)?;
+    /// Writes `source` compressed to the object at `path`, encrypting it if a key was set
+    fn write_object(
+        &self,
+        source: &Path,
+        path: &Path,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<()> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption_key {
+            // Compressed and encrypted on the fly, nothing else is written to disk
+            let compressed = compression.encoder(fs::File::open(source)?, level)?;
+            return encryption::encrypt(key, compressed, fs::File::create(path)?);
         }
+        compression.compress(source, path, level)
+    }
 
-        if let Some(group_name) = &self.group {
-            log::debug!("Setting group {} on {}", group_name, path.display());
-            let gid = resolve_group(group_name)?;
-            chown(path, None, Some(gid))?;
+    /// Reverses `write_object`, decrypting the object if the metadata says it is `encrypted`.
+    /// The plaintext is only ever written to `target`, which is removed if anything fails.
+    fn read_object(
+        &self,
+        path: &Path,
+        target: &Path,
+        compression: Compression,
+        encrypted: bool,
+    ) -> Result<()> {
+        if !encrypted {
+            return compression.decompress(path, target);
+        }
+        #[cfg(feature = "encryption")]
+        {
+            let key = self.encryption_key(path)?;
+            let copied = (|| -> Result<()> {
+                let decrypted = encryption::DecryptReader::new(key, fs::File::open(path)?)?;
+                let mut decompressed = compression.decoder(decrypted)?;
+                let mut writer = io::BufWriter::new(fs::File::create(target)?);
+                io::copy(&mut decompressed, &mut writer)?;
+                writer.flush()?;
+                Ok(())
+            })();
+            if copied.is_err() && target.exists() {
+                fs::remove_file(target)?;
+            }
+            copied
         }
+        #[cfg(not(feature = "encryption"))]
+        bail!(
+            "{} is encrypted but dvs was built without the `encryption` feature",
+            path.display()
+        )
+    }
 
-        Ok(())
+    /// Opens the object at `path` to be read decrypted, but still compressed
+    fn open_object(&self, path: &Path, encrypted: bool) -> Result<Box<dyn io::Read + '_>> {
+        let file = retry_on_stale(|| Ok(fs::File::open(path)?))?;
+        if !encrypted {
+            return Ok(Box::new(file));
+        }
+        #[cfg(feature = "encryption")]
+        {
+            let key = self.encryption_key(path)?;
+            Ok(Box::new(encryption::DecryptReader::new(key, file)?))
+        }
+        #[cfg(not(feature = "encryption"))]
+        bail!(
+            "{} is encrypted but dvs was built without the `encryption` feature",
+            path.display()
+        )
     }
 
-    #[cfg(not(unix))]
-    pub fn apply_perms(&self, _path: impl AsRef<Path>) -> Result<()> {
-        Ok(())
+    /// Objects stored before the index existed can still be found by the hash they are
+    /// stored under. Their metadata isn't at hand, so every way they could have been stored
+    /// is tried and the one hashing back to `hash` gives the full set of hashes, which is then
+    /// indexed for next time.
+    fn index_unindexed(&self, hash: &str) -> Result<Option<Hashes>> {
+        let path = self.object_path(hash)?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let hash = normalize_hash(hash)?;
+        for encrypted in [false, true] {
+            if encrypted && !self.encrypts() {
+                continue;
+            }
+            for compression in [Compression::None, Compression::Zstd] {
+                let hashes = self
+                    .open_object(&path, encrypted)
+                    .and_then(|reader| compression.decoder(reader))
+                    .and_then(|content| Ok(Hashes::from_reader(content, |_| {})?.0));
+                let Ok(hashes) = hashes else {
+                    continue;
+                };
+                if hashes.get_by_alg(self.hash_alg) == Some(hash.as_str()) {
+                    self.write_index(&hashes)?;
+                    return Ok(Some(hashes));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn encryption_key(&self, object: &Path) -> Result<&EncryptionKey> {
+        self.encryption_key.as_ref().ok_or_else(|| {
+            anyhow!(
+                "{} is encrypted but no encryption key is configured",
+                object.display()
+            )
+        })
+    }
+
+    /// Apply configured permissions and group to a path.
+    /// No-op on non-Unix or if neither permissions nor group are set.
+    pub fn apply_perms(&self, path: impl AsRef<Path>) -> Result<()> {
+        apply_perms_to(
+            path.as_ref(),
+            self.permissions.as_deref(),
+            self.group.as_deref(),
+        )
+    }
+
+    /// Same as `apply_perms` but taking the per-object overrides into account.
+    fn apply_object_perms(&self, path: impl AsRef<Path>) -> Result<()> {
+        apply_perms_to(
+            path.as_ref(),
+            self.object_permissions
+                .as_deref()
+                .or(self.permissions.as_deref()),
+            self.object_group.as_deref().or(self.group.as_deref()),
+        )
     }
 
     fn hash_to_path(&self, hashes: &Hashes) -> Result<PathBuf> {
-        let hash = hashes.get_by_alg(self.hash_alg);
-        if hash.len() < 3 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
-            bail!("Invalid hash: {hash}");
-        }
+        let hash = hashes
+            .get_by_alg(self.hash_alg)
+            .ok_or_else(|| anyhow!("No {} hash recorded in {hashes}", self.hash_alg.name()))?;
+        self.object_path(hash)
+    }
+
+    /// Where the object stored under `hash`, of `self.hash_alg`, is
+    fn object_path(&self, hash: &str) -> Result<PathBuf> {
+        let hash = normalize_hash(hash)?;
         let (prefix, suffix) = hash.split_at(2);
         Ok(self.path.join(prefix).join(suffix))
     }
+
+    fn index_path(&self, alg: HashAlg, hash: &str) -> Result<PathBuf> {
+        let hash = normalize_hash(hash)?;
+        let (prefix, suffix) = hash.split_at(2);
+        Ok(self
+            .path
+            .join(INDEX_FOLDER_NAME)
+            .join(alg.name())
+            .join(prefix)
+            .join(suffix))
+    }
+
+    /// Records every hash of the object in the index so it can be looked up by any of them.
+    /// Entries already up to date are left alone.
+    fn write_index(&self, hashes: &Hashes) -> Result<()> {
+        let content = serde_json::to_string(hashes)?;
+        for (alg, hash) in hashes.recorded() {
+            let path = self.index_path(alg, hash)?;
+            if fs::read_to_string(&path).is_ok_and(|existing| existing == content) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+                // index/, index/{alg}/ and index/{alg}/{prefix}/
+                for dir in parent.ancestors().take(3) {
+                    self.apply_perms(dir)?;
+                }
+            }
+            fs::write(&path, &content)?;
+            self.apply_perms(&path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates a hash and returns its canonical lowercase form, so the same object
+/// always maps to the same path whatever the case of the input.
+fn normalize_hash(hash: &str) -> Result<String> {
+    if hash.len() < 3 {
+        bail!("Invalid hash '{hash}': too short");
+    }
+    if !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid hash '{hash}': must only contain hexadecimal characters");
+    }
+    Ok(hash.to_ascii_lowercase())
+}
+
+/// Storage on NFS can transiently fail with a stale file handle when another client
+/// replaced a file or directory we had open. Those are worth a single retry, any other
+/// error is returned as is.
+fn retry_on_stale<T>(mut op: impl FnMut() -> Result<T>) -> Result<T> {
+    match op() {
+        Err(e) if is_stale_file_handle(&e) => {
+            log::warn!("Stale file handle, retrying once: {e:#}");
+            op()
+        }
+        res => res,
+    }
+}
+
+fn is_stale_file_handle(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<io::Error>())
+        .any(|e| e.kind() == io::ErrorKind::StaleNetworkFileHandle)
 }
 
 impl Backend for LocalBackend {
@@ -126,14 +374,21 @@ impl Backend for LocalBackend {
         Ok(())
     }
 
-    fn store(&self, hash: &Hashes, source: &Path, compression: Compression) -> Result<()> {
+    fn store(
+        &self,
+        hash: &Hashes,
+        source: &Path,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<()> {
         let path = self.hash_to_path(hash)?;
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
             self.apply_perms(parent)?;
         }
-        compression.compress(source, &path)?;
-        self.apply_perms(&path)?;
+        retry_on_stale(|| self.write_object(source, &path, compression, level))?;
+        self.apply_object_perms(&path)?;
+        self.write_index(hash)?;
         Ok(())
     }
 
@@ -144,18 +399,25 @@ impl Backend for LocalBackend {
             fs::create_dir_all(parent)?;
             self.apply_perms(parent)?;
         }
-        fs::write(&path, content)?;
-        self.apply_perms(&path)?;
+        retry_on_stale(|| Ok(fs::write(&path, content)?))?;
+        self.apply_object_perms(&path)?;
+        self.write_index(hash)?;
         Ok(())
     }
 
-    fn retrieve(&self, hash: &Hashes, target: &Path, compression: Compression) -> Result<bool> {
+    fn retrieve(
+        &self,
+        hash: &Hashes,
+        target: &Path,
+        compression: Compression,
+        encrypted: bool,
+    ) -> Result<bool> {
         let path = self.hash_to_path(hash)?;
         if path.is_file() {
             if let Some(parent) = target.parent() {
                 fs::create_dir_all(parent)?;
             }
-            compression.decompress(&path, target)?;
+            retry_on_stale(|| self.read_object(&path, target, compression, encrypted))?;
             Ok(true)
         } else {
             Ok(false)
@@ -166,12 +428,46 @@ impl Backend for LocalBackend {
         Ok(self.hash_to_path(hash)?.is_file())
     }
 
+    fn stat(&self, hash: &Hashes) -> Result<Option<ObjectStat>> {
+        let path = self.hash_to_path(hash)?;
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ObjectStat {
+                size: metadata.len(),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn lookup(&self, alg: HashAlg, hash: &str) -> Result<Option<Hashes>> {
+        let index_path = self.index_path(alg, hash)?;
+        if !index_path.is_file() {
+            if alg == self.hash_alg {
+                return self.index_unindexed(hash);
+            }
+            return Ok(None);
+        }
+        let hashes: Hashes = serde_json::from_str(&fs::read_to_string(&index_path)?)?;
+        if self.exists(&hashes)? {
+            Ok(Some(hashes))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn remove(&self, hash: &Hashes) -> Result<()> {
         let path = self.hash_to_path(hash)?;
         if path.is_file() {
             log::debug!("Removing {path:?} from storage");
             fs::remove_file(path)?;
         }
+        for (alg, h) in hash.recorded() {
+            let index_path = self.index_path(alg, h)?;
+            if index_path.is_file() {
+                fs::remove_file(index_path)?;
+            }
+        }
         Ok(())
     }
 
@@ -179,12 +475,29 @@ impl Backend for LocalBackend {
         let path = self.hash_to_path(hash)?;
         if path.is_file() {
             log::debug!("Reading {path:?} from storage");
-            Ok(Some(fs::read(&path)?))
+            Ok(Some(retry_on_stale(|| Ok(fs::read(&path)?))?))
         } else {
             Ok(None)
         }
     }
 
+    #[cfg(feature = "encryption")]
+    fn encrypts(&self) -> bool {
+        self.encryption_key.is_some()
+    }
+
+    fn open(&self, hash: &Hashes, encrypted: bool) -> Result<Option<Box<dyn io::Read + '_>>> {
+        let path = self.hash_to_path(hash)?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(self.open_object(&path, encrypted)?))
+    }
+
+    fn ensure_indexed(&self, hashes: &Hashes) -> Result<()> {
+        self.write_index(hashes)
+    }
+
     fn log_audit(&self, entry: &AuditEntry) -> Result<()> {
         log::debug!("Appending {entry:?} to audit log");
         let audit_path = self.path.join(AUDIT_LOG_FILENAME);
@@ -199,7 +512,7 @@ impl Backend for LocalBackend {
     }
 
     fn read_audit_file(&self, files: &[PathBuf]) -> Result<Vec<AuditEntry>> {
-        let files_to_include: HashSet<_> = HashSet::from_iter(files.iter().cloned());
+        let files_to_include: HashSet<_> = files.iter().map(|f| to_portable_path(f)).collect();
         let audit_path = self.path.join(AUDIT_LOG_FILENAME);
         let f = fs::File::open(&audit_path)?;
         let entries = parse_audit_log(BufReader::new(f), &files_to_include)?;
@@ -212,6 +525,7 @@ mod tests {
     use super::*;
     use crate::audit::{Action, AuditEntry, AuditFile, parse_audit_log};
     use crate::config::Compression;
+    use crate::file::Outcome;
     use crate::hashes::Hashes;
     use std::io::Cursor;
 
@@ -219,6 +533,9 @@ mod tests {
         Hashes {
             blake3: hash.to_string(),
             md5: hash.to_string(),
+            sha256: None,
+            sha512: None,
+            xxh128: None,
         }
     }
 
@@ -240,6 +557,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hash_to_path_normalizes_to_lowercase() {
+        let backend = LocalBackend::new("/tmp/storage", None, None).unwrap();
+        let lower = backend
+            .hash_to_path(&test_hash("d41d8cd98f00b204e9800998ecf8427e"))
+            .unwrap();
+        let upper = backend
+            .hash_to_path(&test_hash("D41D8CD98F00B204E9800998ECF8427E"))
+            .unwrap();
+        assert_eq!(lower, upper);
+        assert_eq!(
+            lower,
+            PathBuf::from("/tmp/storage/d4/1d8cd98f00b204e9800998ecf8427e")
+        );
+
+        let err = backend.hash_to_path(&test_hash("xyz123")).unwrap_err();
+        assert!(err.to_string().contains("hexadecimal"));
+    }
+
     #[test]
     fn init_creates_storage_directory() {
         let tmp = tempfile::tempdir().unwrap();
@@ -264,7 +600,9 @@ mod tests {
         fs::write(&source, b"test content").unwrap();
 
         let hash = test_hash("d41d8cd98f00b204e9800998ecf8427e");
-        backend.store(&hash, &source, Compression::None).unwrap();
+        backend
+            .store(&hash, &source, Compression::None, None)
+            .unwrap();
 
         let stored = storage.join("d4").join("1d8cd98f00b204e9800998ecf8427e");
         assert!(stored.is_file());
@@ -284,13 +622,69 @@ mod tests {
 
         // Retrieve to new location
         let target = tmp.path().join("retrieved.txt");
-        let result = backend.retrieve(&hash, &target, Compression::None).unwrap();
+        let result = backend
+            .retrieve(&hash, &target, Compression::None, false)
+            .unwrap();
 
         // file was copied if result == true
         assert!(result);
         assert_eq!(fs::read(&target).unwrap(), b"stored content");
     }
 
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn failed_decryption_leaves_nothing_behind() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut backend = LocalBackend::new(tmp.path().join("storage"), None, None).unwrap();
+        backend.init().unwrap();
+        backend.set_encryption_key(&"a".repeat(64)).unwrap();
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, b"secret content").unwrap();
+        let hash = Hashes::from(b"secret content".to_vec());
+        backend
+            .store(&hash, &source, Compression::Zstd, None)
+            .unwrap();
+
+        backend.set_encryption_key(&"b".repeat(64)).unwrap();
+        let workdir = tmp.path().join("workdir");
+        fs::create_dir(&workdir).unwrap();
+        let target = workdir.join("retrieved.txt");
+        assert!(
+            backend
+                .retrieve(&hash, &target, Compression::Zstd, true)
+                .is_err()
+        );
+        assert_eq!(fs::read_dir(&workdir).unwrap().count(), 0);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn plaintext_objects_looking_encrypted_are_read_as_is() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut backend = LocalBackend::new(tmp.path().join("storage"), None, None).unwrap();
+        backend.init().unwrap();
+        let content = b"DVSENC1\0 but not actually encrypted".to_vec();
+        let source = tmp.path().join("source.bin");
+        fs::write(&source, &content).unwrap();
+        let hash = Hashes::from(content.clone());
+        backend
+            .store(&hash, &source, Compression::None, None)
+            .unwrap();
+        assert!(!backend.encrypts());
+
+        // Enabling encryption afterwards doesn't change how existing objects are read
+        backend.set_encryption_key(&"a".repeat(64)).unwrap();
+        assert!(backend.encrypts());
+        let target = tmp.path().join("retrieved.bin");
+        assert!(
+            backend
+                .retrieve(&hash, &target, Compression::None, false)
+                .unwrap()
+        );
+        assert_eq!(fs::read(&target).unwrap(), content);
+        assert!(backend.verify(&hash, Compression::None, false).unwrap());
+    }
+
     #[test]
     fn retrieve_returns_false_when_missing() {
         let tmp = tempfile::tempdir().unwrap();
@@ -304,6 +698,7 @@ mod tests {
                 &test_hash("1234567890123456789012"),
                 &target,
                 Compression::None,
+                false,
             )
             .unwrap();
 
@@ -324,6 +719,122 @@ mod tests {
         assert!(backend.exists(&hash).unwrap());
     }
 
+    #[test]
+    fn stat_returns_stored_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let storage = tmp.path().join("storage");
+        let backend = LocalBackend::new(&storage, None, None).unwrap();
+        backend.init().unwrap();
+
+        let hash = test_hash("abc123def456789012345678901234ab");
+        assert_eq!(backend.stat(&hash).unwrap(), None);
+        backend.store_bytes(&hash, b"content").unwrap();
+        assert_eq!(backend.stat(&hash).unwrap(), Some(ObjectStat { size: 7 }));
+    }
+
+    #[test]
+    fn lookup_by_any_recorded_hash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let storage = tmp.path().join("storage");
+        let backend = LocalBackend::new(&storage, None, None).unwrap();
+        backend.init().unwrap();
+
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, b"indexed content").unwrap();
+        let hashes = Hashes::from(b"indexed content".to_vec());
+        backend
+            .store(&hashes, &source, Compression::Zstd, None)
+            .unwrap();
+
+        let sha256 = hashes.sha256.clone().unwrap();
+        for (alg, hash) in hashes.recorded() {
+            let found = backend.lookup(alg, hash).unwrap().unwrap();
+            assert_eq!(found, hashes);
+
+            let target = tmp.path().join(format!("retrieved-{}.txt", alg.name()));
+            assert!(
+                backend
+                    .retrieve(&found, &target, Compression::Zstd, false)
+                    .unwrap()
+            );
+            assert_eq!(fs::read(&target).unwrap(), b"indexed content");
+        }
+
+        // Hashes are case-insensitive on input
+        let found = backend
+            .lookup(HashAlg::Sha256, &sha256.to_ascii_uppercase())
+            .unwrap();
+        assert_eq!(found, Some(hashes.clone()));
+
+        backend.remove(&hashes).unwrap();
+        assert_eq!(backend.lookup(HashAlg::Sha256, &sha256).unwrap(), None);
+    }
+
+    #[test]
+    fn lookup_finds_objects_stored_before_the_index() {
+        let tmp = tempfile::tempdir().unwrap();
+        let storage = tmp.path().join("storage");
+        let backend = LocalBackend::new(&storage, None, None).unwrap();
+        backend.init().unwrap();
+
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, b"legacy content").unwrap();
+        let hashes = Hashes::from(b"legacy content".to_vec());
+        for compression in [Compression::None, Compression::Zstd] {
+            backend.store(&hashes, &source, compression, None).unwrap();
+            fs::remove_dir_all(storage.join(INDEX_FOLDER_NAME)).unwrap();
+
+            let sha256 = hashes.sha256.as_deref().unwrap();
+            assert_eq!(backend.lookup(HashAlg::Sha256, sha256).unwrap(), None);
+            // It is stored under its blake3 though
+            let blake3 = &hashes.blake3;
+            assert_eq!(
+                backend.lookup(HashAlg::Blake3, blake3).unwrap(),
+                Some(hashes.clone()),
+                "{compression:?}"
+            );
+            // and is now indexed
+            assert_eq!(
+                backend.lookup(HashAlg::Sha256, sha256).unwrap(),
+                Some(hashes.clone())
+            );
+            backend.remove(&hashes).unwrap();
+        }
+    }
+
+    #[test]
+    fn verify_detects_corrupted_object() {
+        let tmp = tempfile::tempdir().unwrap();
+        let storage = tmp.path().join("storage");
+        let backend = LocalBackend::new(&storage, None, None).unwrap();
+        backend.init().unwrap();
+
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, b"precious content").unwrap();
+        let hashes = Hashes::from(b"precious content".to_vec());
+        backend
+            .store(&hashes, &source, Compression::Zstd, None)
+            .unwrap();
+        assert!(backend.verify(&hashes, Compression::Zstd, false).unwrap());
+
+        // Valid zstd data but of the wrong content
+        let stored = backend.hash_to_path(&hashes).unwrap();
+        fs::write(
+            &stored,
+            zstd::encode_all(&b"tampered content"[..], 0).unwrap(),
+        )
+        .unwrap();
+        assert!(!backend.verify(&hashes, Compression::Zstd, false).unwrap());
+
+        // Not even zstd anymore
+        fs::write(&stored, b"garbage").unwrap();
+        assert!(!backend.verify(&hashes, Compression::Zstd, false).unwrap());
+
+        // Missing
+        backend.remove(&hashes).unwrap();
+        assert!(!backend.verify(&hashes, Compression::Zstd, false).unwrap());
+    }
+
     #[test]
     fn remove_deletes_stored_file() {
         let tmp = tempfile::tempdir().unwrap();
@@ -376,6 +887,35 @@ mod tests {
         assert_eq!(mode & 0o777, 0o750);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn object_permissions_override_only_applies_to_objects() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let storage = tmp.path().join("storage");
+        let mut backend = LocalBackend::new(&storage, Some("750".to_string()), None).unwrap();
+        backend.init().unwrap();
+        backend
+            .override_object_permissions(Some("700".to_string()), None)
+            .unwrap();
+
+        let hash = test_hash("abc123def456789012345678901234ab");
+        backend.store_bytes(&hash, b"content").unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        let stored = storage.join("ab").join("c123def456789012345678901234ab");
+        assert_eq!(mode(&stored), 0o700);
+        assert_eq!(mode(&storage.join("ab")), 0o750);
+
+        // Invalid overrides are rejected
+        assert!(
+            backend
+                .override_object_permissions(Some("999".to_string()), None)
+                .is_err()
+        );
+    }
+
     #[test]
     fn log_audit_appends_to_jsonl() {
         let tmp = tempfile::tempdir().unwrap();
@@ -394,6 +934,7 @@ mod tests {
                 hashes: hash.clone(),
             },
             action: Action::Add,
+            outcome: None,
         };
 
         let entry2 = AuditEntry {
@@ -405,6 +946,7 @@ mod tests {
                 hashes: hash.clone(),
             },
             action: Action::Add,
+            outcome: None,
         };
 
         backend.log_audit(&entry1).unwrap();
@@ -425,4 +967,69 @@ mod tests {
         assert_eq!(entries[1].timestamp, 2000000000);
         assert_eq!(entries[1].user, "bob");
     }
+
+    #[test]
+    fn parse_audit_log_reads_entries_from_any_version() {
+        let hashes = serde_json::to_string(&test_hash("abc123def456789012345678901234ab")).unwrap();
+        let content = [
+            // Adds written before outcomes were recorded
+            format!(
+                r#"{{"operation_id":"op-1","timestamp":1,"user":"alice","file":{{"path":"a.csv","hashes":{hashes}}},"action":"add"}}"#
+            ),
+            format!(
+                r#"{{"operation_id":"op-2","timestamp":2,"user":"bob","file":{{"path":"a.csv","hashes":{hashes}}},"action":"get","outcome":"copied"}}"#
+            ),
+            // From a future version, with an action and a field we don't know about
+            format!(
+                r#"{{"operation_id":"op-3","timestamp":3,"user":"carol","file":{{"path":"a.csv","hashes":{hashes}}},"action":"remove","reason":"cleanup"}}"#
+            ),
+            // Cut short by a crash
+            r#"{"operation_id":"op-4","timestamp":4,"us"#.to_string(),
+            format!(
+                r#"{{"operation_id":"op-5","timestamp":5,"user":"dave","file":{{"path":"b.csv","hashes":{hashes}}},"action":"add"}}"#
+            ),
+        ]
+        .join("\n");
+
+        let entries = parse_audit_log(Cursor::new(&content), &HashSet::new()).unwrap();
+        let actions: Vec<_> = entries
+            .iter()
+            .map(|e| (e.operation_id.as_str(), e.action, e.outcome))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("op-1", Action::Add, None),
+                ("op-2", Action::Get, Some(Outcome::Copied)),
+                ("op-3", Action::Unknown, None),
+                ("op-5", Action::Add, None),
+            ]
+        );
+
+        let only_b = HashSet::from([PathBuf::from("b.csv")]);
+        let entries = parse_audit_log(Cursor::new(&content), &only_b).unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn retry_on_stale_retries_stale_file_handles_once() {
+        let mut attempts = 0;
+        let res = retry_on_stale(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(io::Error::from(io::ErrorKind::StaleNetworkFileHandle).into())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res.unwrap(), 2);
+
+        let mut attempts = 0;
+        let res: Result<()> = retry_on_stale(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied).into())
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 1);
+    }
 }
@@ -1,35 +1,132 @@
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
-use crate::Hashes;
 use crate::audit::AuditEntry;
 use crate::config::Compression;
-use anyhow::Result;
+use crate::hashes::blake3_reader;
+use crate::{HashAlg, Hashes};
+use anyhow::{Result, bail};
 
 pub mod local;
 
+/// What a backend knows about a stored object without reading it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectStat {
+    /// Size of the object as stored, so after compression
+    pub size: u64,
+}
+
 pub trait Backend: Send + Sync {
     /// Initialize the backend storage (create directories, set permissions, etc.)
     fn init(&self) -> Result<()>;
 
-    /// Store file to backend by hash, optionally compressing.
-    fn store(&self, hash: &Hashes, source: &Path, compression: Compression) -> Result<()>;
+    /// Store file to backend by hash, optionally compressing at the given level.
+    fn store(
+        &self,
+        hash: &Hashes,
+        source: &Path,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<()>;
 
     /// Store raw bytes to backend by hash (for rollback).
     fn store_bytes(&self, hash: &Hashes, content: &[u8]) -> Result<()>;
 
-    /// Retrieve content by hash to target path, optionally decompressing.
+    /// Retrieve content by hash to target path, optionally decompressing and decrypting
+    /// if the metadata says the object was `encrypted`.
     /// Returns true if the file was copied to the target path.
-    fn retrieve(&self, hash: &Hashes, target: &Path, compression: Compression) -> Result<bool>;
+    fn retrieve(
+        &self,
+        hash: &Hashes,
+        target: &Path,
+        compression: Compression,
+        encrypted: bool,
+    ) -> Result<bool>;
+
+    /// Whether the objects stored from now on are encrypted, to record in their metadata.
+    fn encrypts(&self) -> bool {
+        false
+    }
 
     /// Check if the file exists in the backend
     fn exists(&self, hash: &Hashes) -> Result<bool>;
 
+    /// Get the metadata of a stored object without reading its content.
+    /// Returns None if not found.
+    fn stat(&self, hash: &Hashes) -> Result<Option<ObjectStat>>;
+
+    /// Find a stored object from the hash of any algorithm recorded for it.
+    /// Returns the full set of hashes, usable with the other methods, or None if
+    /// no such object is stored.
+    fn lookup(&self, alg: HashAlg, hash: &str) -> Result<Option<Hashes>>;
+
+    /// Records the hashes of an object that is already stored so it can be looked up by any
+    /// of them, for objects stored before some algorithms were recorded.
+    fn ensure_indexed(&self, _hashes: &Hashes) -> Result<()> {
+        Ok(())
+    }
+
     /// Remove content by hash (for rollback). Best-effort, may silently fail.
     fn remove(&self, hash: &Hashes) -> Result<()>;
 
     /// Read content by hash. Returns None if not found.
     fn read(&self, hash: &Hashes) -> Result<Option<Vec<u8>>>;
 
+    /// Opens the stored object to be read as it was before encryption, if it was `encrypted`,
+    /// but still compressed. Returns None if not found.
+    /// The default implementation reads the whole object in memory, backends should override it
+    /// to stream it instead.
+    fn open(&self, hash: &Hashes, encrypted: bool) -> Result<Option<Box<dyn Read + '_>>> {
+        if encrypted {
+            bail!("This backend doesn't support encrypted objects");
+        }
+        Ok(self
+            .read(hash)?
+            .map(|content| Box::new(io::Cursor::new(content)) as Box<dyn Read>))
+    }
+
+    /// Check that the stored content still hashes to `hash`.
+    /// Returns false if the object is missing, can't be decompressed or doesn't match.
+    /// The object is read through decryption and decompression as `open` gives it, so it is
+    /// only streamed rather than held in memory if the backend overrides `open`.
+    fn verify(&self, hash: &Hashes, compression: Compression, encrypted: bool) -> Result<bool> {
+        let Some(reader) = self.open(hash, encrypted)? else {
+            return Ok(false);
+        };
+        let hashed = compression
+            .decoder(reader)
+            .and_then(|content| Ok(blake3_reader(content)?));
+        match hashed {
+            Ok((blake3, _)) => Ok(blake3 == hash.blake3),
+            Err(e) => {
+                log::debug!("Failed to read back stored object {hash}: {e}");
+                Ok(false)
+            }
+        }
+    }
+
+    /// How an object already stored was compressed and whether it was encrypted, found by
+    /// checking which way of reading it gives back its content. Objects are shared by every
+    /// file with the same content, so one stored before the settings changed stays as it was.
+    /// Returns None if it is missing or can't be read, eg encrypted with no key configured.
+    fn stored_as(&self, hash: &Hashes) -> Result<Option<(Compression, bool)>> {
+        // Decrypting and zstd fail on the first bytes of anything else, so those come first
+        for encrypted in [true, false] {
+            if encrypted && !self.encrypts() {
+                continue;
+            }
+            for compression in [Compression::Zstd, Compression::None] {
+                // Opening fails too when assuming it's encrypted if it isn't
+                match self.verify(hash, compression, encrypted) {
+                    Ok(true) => return Ok(Some((compression, encrypted))),
+                    Ok(false) => {}
+                    Err(e) => log::debug!("Failed to open stored object {hash}: {e}"),
+                }
+            }
+        }
+        Ok(None)
+    }
+
     /// Log an audit entry to the backend's audit log.
     fn log_audit(&self, entry: &AuditEntry) -> Result<()>;
 
@@ -1,11 +1,12 @@
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::Path;
 
 use crate::backends::Backend as BackendTrait;
 use crate::backends::local::LocalBackend;
 use crate::paths::{CONFIG_FILE_NAME, DEFAULT_FOLDER_NAME, find_repo_root};
-use anyhow::{Context, Result};
+use crate::sniff::DEFAULT_TEXT_EXTENSIONS;
+use anyhow::{Context, Result, bail};
 use fs_err as fs;
 use serde::{Deserialize, Serialize};
 
@@ -18,41 +19,104 @@ pub enum Compression {
 }
 
 impl Compression {
-    pub fn compress(&self, source: &Path, dest: &Path) -> Result<()> {
-        match self {
-            Compression::None => {
-                fs::copy(source, dest)?;
-                Ok(())
-            }
-            Compression::Zstd => {
-                let input = fs::File::open(source)?;
-                let output = fs::File::create(dest)?;
-
-                let mut encoder = zstd::stream::read::Encoder::new(input, 0)?;
-                let mut writer = io::BufWriter::new(output);
-                io::copy(&mut encoder, &mut writer)?;
-                writer.flush()?;
-                Ok(())
-            }
+    /// `level` is only used by zstd, `None` uses its default level
+    pub fn compress(&self, source: &Path, dest: &Path, level: Option<i32>) -> Result<()> {
+        if *self == Compression::None {
+            fs::copy(source, dest)?;
+            return Ok(());
         }
+        let mut encoder = self.encoder(fs::File::open(source)?, level)?;
+        let mut writer = io::BufWriter::new(fs::File::create(dest)?);
+        io::copy(&mut encoder, &mut writer)?;
+        writer.flush()?;
+        Ok(())
     }
 
     pub fn decompress(&self, source: &Path, dest: &Path) -> Result<()> {
-        match self {
-            Compression::None => {
-                fs::copy(source, dest)?;
-                Ok(())
-            }
-            Compression::Zstd => {
-                let input = fs::File::open(source)?;
-                let output = fs::File::create(dest)?;
-
-                let mut decoder = zstd::stream::read::Decoder::new(input)?;
-                let mut writer = io::BufWriter::new(output);
-                io::copy(&mut decoder, &mut writer)?;
-                writer.flush()?;
-                Ok(())
+        if *self == Compression::None {
+            fs::copy(source, dest)?;
+            return Ok(());
+        }
+        let mut decoder = self.decoder(fs::File::open(source)?)?;
+        let mut writer = io::BufWriter::new(fs::File::create(dest)?);
+        io::copy(&mut decoder, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Wraps `reader` so reading from it gives the compressed content
+    pub fn encoder<'a>(
+        &self,
+        reader: impl Read + 'a,
+        level: Option<i32>,
+    ) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Zstd => Box::new(zstd::stream::read::Encoder::new(
+                reader,
+                level.unwrap_or(0),
+            )?),
+        })
+    }
+
+    /// Wraps `reader` so reading from it gives the decompressed content
+    pub fn decoder<'a>(&self, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        })
+    }
+}
+
+/// The `compression` setting of the config: `none`, `zstd` or `zstd:<level>`, eg `zstd:19`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "String", into = "String")]
+struct CompressionSetting {
+    compression: Compression,
+    level: Option<i32>,
+}
+
+impl TryFrom<String> for CompressionSetting {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let (name, level) = match value.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (value.as_str(), None),
+        };
+        let compression = match name {
+            "none" => Compression::None,
+            "zstd" => Compression::Zstd,
+            _ => bail!("Unknown compression `{value}`, expected `none`, `zstd` or `zstd:<level>`"),
+        };
+        let level = match (compression, level) {
+            (_, None) => None,
+            (Compression::None, Some(_)) => bail!("Compression `none` doesn't take a level"),
+            (Compression::Zstd, Some(level)) => {
+                let range = zstd::compression_level_range();
+                match level.parse::<i32>() {
+                    Ok(l) if range.contains(&l) => Some(l),
+                    _ => bail!(
+                        "Invalid zstd compression level `{level}`, expected an integer between {} and {}",
+                        range.start(),
+                        range.end()
+                    ),
+                }
             }
+        };
+        Ok(Self { compression, level })
+    }
+}
+
+impl From<CompressionSetting> for String {
+    fn from(setting: CompressionSetting) -> Self {
+        let name = match setting.compression {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+        };
+        match setting.level {
+            Some(level) => format!("{name}:{level}"),
+            None => name.to_string(),
         }
     }
 }
@@ -65,15 +129,35 @@ pub enum Backend {
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Config {
-    /// Compression algorithm to use for files in the storage directory
-    compression: Compression,
+    /// Compression algorithm to use for files in the storage directory,
+    /// optionally with a level for zstd like `zstd:19`
+    compression: CompressionSetting,
     /// By default, all the metadata files (the .dvs files) will be stored in a `.dvs` folder
     /// at the root of the repository
     /// If this option is set, dvs will use that folder name instead of `.dvs`
     metadata_folder_name: Option<String>,
+    /// Adding more files than this at once asks for confirmation first.
+    /// Defaults to `DEFAULT_ADD_CONFIRMATION_THRESHOLD`
+    add_confirmation_threshold: Option<usize>,
+    /// Files with these extensions are expected to be text and adding one that looks binary,
+    /// like a gzip file named `.csv`, warns. Set to an empty list to disable the check.
+    /// Defaults to `DEFAULT_TEXT_EXTENSIONS`
+    text_extensions: Option<Vec<String>>,
+    /// A command to validate files before adding them, as the program and its arguments,
+    /// eg `["python", "check_csv.py"]`. The path of the file is appended and files it exits
+    /// with an error for are rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validate_command: Option<Vec<String>>,
+    /// Name of the environment variable holding the key stored objects are encrypted with,
+    /// 32 random bytes written as 64 hex characters.
+    /// The key itself is never written in the config. Requires the `encryption` feature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption_key_env: Option<String>,
     backend: Backend,
 }
 
+pub const DEFAULT_ADD_CONFIRMATION_THRESHOLD: usize = 1000;
+
 impl Config {
     pub fn new_local(
         path: impl AsRef<Path>,
@@ -82,8 +166,12 @@ impl Config {
     ) -> Result<Config> {
         let backend = LocalBackend::new(path.as_ref(), permissions, group)?;
         Ok(Config {
-            compression: Compression::Zstd,
+            compression: CompressionSetting::default(),
             metadata_folder_name: None,
+            add_confirmation_threshold: None,
+            text_extensions: None,
+            validate_command: None,
+            encryption_key_env: None,
             backend: Backend::Local(backend),
         })
     }
@@ -106,8 +194,12 @@ impl Config {
                 Err(e) => return Some(Err(e.into())),
             };
             Some(
-                toml::from_str(&content)
-                    .with_context(|| format!("Failed to parse {}", config_path.display())),
+                toml::from_str::<Config>(&content)
+                    .with_context(|| format!("Failed to parse {}", config_path.display()))
+                    .and_then(|mut config| {
+                        config.load_encryption_key()?;
+                        Ok(config)
+                    }),
             )
         } else {
             log::debug!("No config file found at {}", config_path.display());
@@ -127,12 +219,86 @@ impl Config {
         }
     }
 
+    pub fn add_confirmation_threshold(&self) -> usize {
+        self.add_confirmation_threshold
+            .unwrap_or(DEFAULT_ADD_CONFIRMATION_THRESHOLD)
+    }
+
+    pub fn text_extensions(&self) -> Vec<String> {
+        self.text_extensions.clone().unwrap_or_else(|| {
+            DEFAULT_TEXT_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        })
+    }
+
+    pub fn validate_command(&self) -> Option<&[String]> {
+        self.validate_command.as_deref()
+    }
+
     pub fn compression(&self) -> Compression {
-        self.compression
+        self.compression.compression
+    }
+
+    /// The zstd level to compress with, `None` uses the zstd default
+    pub fn compression_level(&self) -> Option<i32> {
+        self.compression.level
     }
 
     pub fn set_compression(&mut self, compression: Compression) {
-        self.compression = compression;
+        self.compression = CompressionSetting {
+            compression,
+            level: None,
+        };
+    }
+
+    /// Stores the objects of this invocation with different permissions and/or group
+    /// than the configured ones. This is not saved in the config file.
+    pub fn override_object_permissions(
+        &mut self,
+        permissions: Option<String>,
+        group: Option<String>,
+    ) -> Result<()> {
+        match &mut self.backend {
+            Backend::Local(b) => b.override_object_permissions(permissions, group),
+        }
+    }
+
+    /// Reads the key from the environment variable named in the config, if any
+    fn load_encryption_key(&mut self) -> Result<()> {
+        let Some(var) = self.encryption_key_env.clone() else {
+            return Ok(());
+        };
+        #[cfg(feature = "encryption")]
+        {
+            let key = std::env::var(&var).unwrap_or_default();
+            if key.is_empty() {
+                bail!("Objects are encrypted with the key in ${var} but it is not set");
+            }
+            self.set_encryption_key(&key)
+                .with_context(|| format!("Invalid encryption key in ${var}"))
+        }
+        #[cfg(not(feature = "encryption"))]
+        bail!(
+            "Objects are encrypted with the key in ${var} but dvs was built without the \
+             `encryption` feature"
+        )
+    }
+
+    /// Encrypt the objects stored from now on with `key`, given as 64 hex characters
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&mut self, key: &str) -> Result<()> {
+        match &mut self.backend {
+            Backend::Local(b) => b.set_encryption_key(key),
+        }
+    }
+
+    /// Where the objects are stored
+    pub fn storage_path(&self) -> &Path {
+        match &self.backend {
+            Backend::Local(b) => b.path(),
+        }
     }
 
     pub fn backend(&self) -> &dyn BackendTrait {
@@ -218,4 +384,47 @@ mod tests {
         let loaded = Config::find(&root).unwrap().unwrap();
         assert_eq!(loaded.metadata_folder_name(), ".custom_dvs");
     }
+
+    #[test]
+    fn compression_setting_accepts_a_zstd_level() {
+        let (_tmp, root) = create_temp_git_repo();
+        let mut config = Config::new_local(root.join(".storage"), None, None).unwrap();
+        config.compression = CompressionSetting::try_from("zstd:19".to_string()).unwrap();
+        config.save(&root).unwrap();
+        assert!(
+            fs::read_to_string(root.join(CONFIG_FILE_NAME))
+                .unwrap()
+                .contains(r#"compression = "zstd:19""#)
+        );
+
+        let loaded = Config::find(&root).unwrap().unwrap();
+        assert_eq!(loaded.compression(), Compression::Zstd);
+        assert_eq!(loaded.compression_level(), Some(19));
+
+        for (value, expected) in [
+            ("zstd", Some((Compression::Zstd, None))),
+            ("none", Some((Compression::None, None))),
+            ("zstd:-5", Some((Compression::Zstd, Some(-5)))),
+            ("zstd:99", None),
+            ("zstd:high", None),
+            ("none:3", None),
+            ("gzip", None),
+        ] {
+            let parsed = CompressionSetting::try_from(value.to_string())
+                .ok()
+                .map(|s| (s.compression, s.level));
+            assert_eq!(parsed, expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn encrypted_config_requires_the_key() {
+        let (_tmp, root) = create_temp_git_repo();
+        let mut config = Config::new_local(root.join(".storage"), None, None).unwrap();
+        config.encryption_key_env = Some("DVS_TEST_UNSET_KEY".to_string());
+        config.save(&root).unwrap();
+
+        let err = Config::find(&root).unwrap().unwrap_err();
+        assert!(err.to_string().contains("$DVS_TEST_UNSET_KEY"), "{err}");
+    }
 }
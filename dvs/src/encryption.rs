@@ -0,0 +1,243 @@
+use std::io::{self, Read, Write};
+
+use anyhow::{Result, anyhow, bail};
+use chacha20poly1305::XChaCha20Poly1305;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, OsRng};
+
+/// Encrypted objects start with this, as a format version.
+/// Whether an object is encrypted is recorded in its metadata, not guessed from it.
+const MAGIC: &[u8; 8] = b"DVSENC1\0";
+/// XChaCha20 nonces are 24 bytes, the stream construction uses 5 of them for its counter
+const NONCE_SIZE: usize = 19;
+const TAG_SIZE: usize = 16;
+/// Objects are encrypted by chunks of that size so we never hold a whole file in memory
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The key objects are encrypted with. It has to be 32 random bytes: deriving it from
+/// a passphrase would let anyone with access to the storage brute-force weak ones.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Parses the key written as 64 hex characters, eg from `openssl rand -hex 32`
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        let mut key = [0; 32];
+        if hex.len() != 2 * key.len() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!(
+                "The encryption key must be 32 random bytes written as 64 hex characters, \
+                 eg generated with `openssl rand -hex 32`"
+            );
+        }
+        for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).expect("checked as ascii");
+            *byte = u8::from_str_radix(pair, 16).expect("checked as hex");
+        }
+        Ok(Self(key))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+/// Never print the key
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Writes everything readable from `input` encrypted to `output`, as the magic, a random nonce
+/// and the chunks. The last chunk is always shorter than `CHUNK_SIZE`, possibly empty,
+/// so truncating the output is detected.
+pub fn encrypt(key: &EncryptionKey, mut input: impl Read, output: impl Write) -> Result<()> {
+    let mut nonce = [0; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    let mut encryptor = EncryptorBE32::from_aead(key.cipher(), &nonce.into());
+
+    let mut output = io::BufWriter::new(output);
+    output.write_all(MAGIC)?;
+    output.write_all(&nonce)?;
+
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = read_full(&mut input, &mut buffer)?;
+        if read < CHUNK_SIZE {
+            let chunk = encryptor
+                .encrypt_last(&buffer[..read])
+                .map_err(|_| anyhow!("Failed to encrypt object"))?;
+            output.write_all(&chunk)?;
+            break;
+        }
+        let chunk = encryptor
+            .encrypt_next(buffer.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt object"))?;
+        output.write_all(&chunk)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Reverses `encrypt` as it is read, one chunk at a time.
+/// Reading fails if the content was modified or the key is wrong, and only content
+/// that was authenticated is ever returned.
+pub struct DecryptReader<R> {
+    input: R,
+    /// `None` once the last chunk was decrypted
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    pub fn new(key: &EncryptionKey, mut input: R) -> Result<Self> {
+        let mut header = [0; MAGIC.len() + NONCE_SIZE];
+        if read_full(&mut input, &mut header)? < header.len() || !header.starts_with(MAGIC) {
+            bail!("Not an encrypted object");
+        }
+        let nonce: [u8; NONCE_SIZE] = header[MAGIC.len()..].try_into().expect("nonce size");
+        Ok(Self {
+            input,
+            decryptor: Some(DecryptorBE32::from_aead(key.cipher(), &nonce.into())),
+            chunk: Vec::new(),
+            position: 0,
+        })
+    }
+
+    fn decrypt_next_chunk(&mut self) -> io::Result<()> {
+        let Some(decryptor) = &mut self.decryptor else {
+            return Ok(());
+        };
+        let mut buffer = vec![0; CHUNK_SIZE + TAG_SIZE];
+        let read = read_full(&mut self.input, &mut buffer)?;
+        let decrypted = if read < buffer.len() {
+            let decryptor = self.decryptor.take().expect("checked above");
+            decryptor.decrypt_last(&buffer[..read])
+        } else {
+            decryptor.decrypt_next(buffer.as_slice())
+        };
+        self.chunk = decrypted.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Failed to decrypt object, wrong key or corrupted",
+            )
+        })?;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.decrypt_next_chunk()?;
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Reads until `buffer` is full or the end of `reader`, returning how many bytes were read
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buffer.len() {
+        match reader.read(&mut buffer[total..]) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key(byte: char) -> EncryptionKey {
+        EncryptionKey::from_hex(&byte.to_string().repeat(64)).unwrap()
+    }
+
+    fn decrypt_bytes(key: &EncryptionKey, content: &[u8]) -> Result<Vec<u8>> {
+        let mut decrypted = Vec::new();
+        DecryptReader::new(key, content)?.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    fn roundtrip(key: &EncryptionKey, content: &[u8]) -> (Vec<u8>, Result<Vec<u8>>) {
+        let mut stored = Vec::new();
+        encrypt(key, content, &mut stored).unwrap();
+        let decrypted = decrypt_bytes(key, &stored);
+        (stored, decrypted)
+    }
+
+    #[test]
+    fn encrypt_roundtrips_around_chunk_boundaries() {
+        let key = test_key('a');
+        for size in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            let content: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let (stored, decrypted) = roundtrip(&key, &content);
+            assert!(stored.starts_with(MAGIC));
+            assert_eq!(decrypted.unwrap(), content, "{size}");
+        }
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_key_and_tampering() {
+        let key = test_key('a');
+        let content = vec![7; CHUNK_SIZE * 2 + 10];
+        let (stored, _) = roundtrip(&key, &content);
+
+        assert!(decrypt_bytes(&test_key('b'), &stored).is_err());
+
+        let mut tampered = stored.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(decrypt_bytes(&key, &tampered).is_err());
+
+        // Dropping the last chunk is noticed too
+        let truncated = &stored[..MAGIC.len() + NONCE_SIZE + 2 * (CHUNK_SIZE + TAG_SIZE)];
+        assert!(decrypt_bytes(&key, truncated).is_err());
+    }
+
+    #[test]
+    fn same_content_encrypts_differently() {
+        let key = test_key('a');
+        let (first, _) = roundtrip(&key, b"hello");
+        let (second, _) = roundtrip(&key, b"hello");
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn keys_must_be_32_hex_bytes() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899AABBCCDDEEFF";
+        let key = EncryptionKey::from_hex(&format!("{hex}\n")).unwrap();
+        assert_eq!(key.0[1], 0x11);
+        assert_eq!(key.0[31], 0xff);
+
+        for invalid in [
+            "correct horse battery staple",
+            &hex[..62],
+            &format!("{hex}00"),
+            &hex.replace('0', "g"),
+        ] {
+            assert!(EncryptionKey::from_hex(invalid).is_err(), "{invalid}");
+        }
+    }
+}
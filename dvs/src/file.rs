@@ -1,13 +1,16 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 use crate::audit::{AuditEntry, AuditFile};
 use crate::backends::Backend;
 use crate::config::Compression;
 use crate::gitignore::add_to_gitignore;
-use crate::hashes::Hashes;
-use crate::paths::DvsPaths;
-use anyhow::{Context, Result, bail};
+use crate::hashes::{HashOptions, Hashes, blake3_file};
+use crate::paths::{DvsPaths, special_file_kind};
+use crate::sniff::sniff_binary_text_file;
+use anyhow::{Context, Result, anyhow, bail};
 use fs_err as fs;
+use globset::GlobSet;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use walkdir::WalkDir;
@@ -20,8 +23,29 @@ pub enum Outcome {
     Copied,
     /// File was already present (no action needed).
     Present,
+    /// File was already tracked and left untouched, only when adding with `if_absent`.
+    Skipped,
 }
 
+/// A stored object is missing or doesn't match its metadata, as opposed to the operation
+/// failing for other reasons. Callers can find it in the error chain.
+#[derive(Debug)]
+pub struct IntegrityError(String);
+
+impl IntegrityError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl std::fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
@@ -33,6 +57,60 @@ pub enum Status {
     Absent,
     /// Local file exists but differs from stored version.
     Unsynced,
+    /// Local file exists but we don't have the permission to read it,
+    /// so we can't tell whether it matches.
+    Unreadable,
+}
+
+/// Reports hashing progress of large files
+pub struct HashProgress<'a> {
+    /// Only files at least this big report progress
+    pub min_size: u64,
+    /// Called with the file path, the number of bytes hashed so far and the file size
+    pub callback: &'a dyn Fn(&Path, u64, u64),
+}
+
+/// Options for `get_status`
+#[derive(Default)]
+pub struct StatusOptions<'a> {
+    /// Tracked files whose repo-relative path matches are skipped entirely
+    pub exclude: GlobSet,
+    pub progress: Option<&'a HashProgress<'a>>,
+}
+
+/// Options for `add_files`
+#[derive(Default)]
+pub struct AddOptions<'a> {
+    pub message: Option<String>,
+    pub annotations: BTreeMap<String, String>,
+    pub compression: Compression,
+    /// The zstd level to compress with, `None` uses the zstd default
+    pub compression_level: Option<i32>,
+    /// Track zero-byte files instead of rejecting them
+    pub allow_empty: bool,
+    /// Record files that fail to be added in the results and carry on with the others
+    /// instead of stopping at the first error
+    pub keep_going: bool,
+    /// Only track files that are not tracked yet, leaving the others untouched
+    /// even if they changed
+    pub if_absent: bool,
+    /// Warn when files with these extensions don't look like text
+    pub text_extensions: Vec<String>,
+    /// A command run on every file before adding anything, with the file path appended to
+    /// it. Files it exits with an error for are rejected
+    pub validate_command: Option<Vec<String>>,
+    pub progress: Option<&'a HashProgress<'a>>,
+    pub hashing: HashOptions,
+}
+
+/// Options for `get_files`
+#[derive(Default)]
+pub struct GetOptions {
+    /// Replace local files that differ from their tracked version instead of refusing
+    pub overwrite: bool,
+    /// Retrieve all the files or none of them: they are staged first and only moved
+    /// to the working tree once every one of them was retrieved and verified
+    pub atomic: bool,
 }
 
 /// The dvs metadata for a given file
@@ -42,30 +120,87 @@ pub struct FileMetadata {
     pub size: u64,
     pub created_by: String,
     pub add_time: String,
+    /// When the file was last modified before being added, ie when the data was produced.
+    /// Missing from metadata written by older versions of dvs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
     pub compression: Compression,
+    /// The zstd level the object was compressed with, when not the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+    /// Whether the stored object is encrypted
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    /// Arbitrary user-provided key/value pairs (dataset version, source URL...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
 }
 
-impl PartialEq for FileMetadata {
-    fn eq(&self, other: &Self) -> bool {
-        self.hashes == other.hashes && self.size == other.size
+impl FileMetadata {
+    /// Whether both describe the same content, see `Hashes::matches`
+    pub fn matches(&self, other: &FileMetadata) -> bool {
+        self.size == other.size && self.hashes.matches(&other.hashes)
+    }
+
+    /// Whether the file at `path` has the content described by this metadata.
+    /// Only blake3 is computed since it is always recorded, the other hashes are only
+    /// needed when storing.
+    pub fn matches_file(
+        &self,
+        path: &Path,
+        progress: Option<&HashProgress>,
+        hashing: &HashOptions,
+    ) -> Result<bool> {
+        let size = fs::metadata(path)?.len();
+        if size != self.size {
+            return Ok(false);
+        }
+        let progress = progress.filter(|p| size >= p.min_size);
+        let (blake3, _) = blake3_file(path, hashing, |done| {
+            if let Some(p) = progress {
+                (p.callback)(path, done, size);
+            }
+        })?;
+        Ok(blake3 == self.hashes.blake3)
     }
-}
 
-impl FileMetadata {
     pub fn from_file(
         path: impl AsRef<Path>,
         compression: Compression,
         message: Option<String>,
     ) -> Result<Self> {
-        if !path.as_ref().is_file() {
-            bail!("Path {} is not a file", path.as_ref().display());
+        Self::from_file_with_progress(path, compression, message, None, &HashOptions::default())
+    }
+
+    /// Same as `from_file` but reports hashing progress if the file is large enough
+    /// and reads the file as set in `hashing`
+    pub fn from_file_with_progress(
+        path: impl AsRef<Path>,
+        compression: Compression,
+        message: Option<String>,
+        progress: Option<&HashProgress>,
+        hashing: &HashOptions,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            bail!("Path {} is not a file", path.display());
         }
 
-        let content = fs::read(path.as_ref())?;
-        let size = content.len() as u64;
-        let hashes = Hashes::from(content);
+        let file_metadata = fs::metadata(path)?;
+        let expected_size = file_metadata.len();
+        let mtime = file_metadata
+            .modified()
+            .ok()
+            .and_then(|t| jiff::Timestamp::try_from(t).ok())
+            .map(|t| t.to_string());
+        let progress = progress.filter(|p| expected_size >= p.min_size);
+        let (hashes, size) = Hashes::from_file(path, hashing, |done| {
+            if let Some(p) = progress {
+                (p.callback)(path, done, expected_size);
+            }
+        })?;
         let created_by = whoami::username()?;
         let add_time = jiff::Timestamp::now().to_string();
 
@@ -74,13 +209,25 @@ impl FileMetadata {
             size,
             created_by,
             add_time,
+            mtime,
             message,
             compression,
+            compression_level: None,
+            encrypted: false,
+            annotations: BTreeMap::new(),
         })
     }
 
+    /// Reads the metadata stored in a `.dvs` file
+    pub fn load(dvs_file_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_reader(fs::File::open(
+            dvs_file_path.as_ref(),
+        )?)?)
+    }
+
     /// Returns whether the file already existed in the dvs folder and therefore is an update.
-    /// Copies the source file to storage and saves metadata atomically (both succeed or neither).
+    /// Copies the source file to storage, unless its content is already stored, and saves
+    /// metadata atomically (both succeed or neither).
     pub fn save(
         &self,
         operation_id: Uuid,
@@ -102,44 +249,87 @@ impl FileMetadata {
 
         if dvs_file_exists && storage_exists {
             // we read the file anyway to make sure it's not 2 files having the same hash
-            let existing: FileMetadata = serde_json::from_reader(fs::File::open(&dvs_file_path)?)?;
-            if existing == *self {
+            let existing = FileMetadata::load(&dvs_file_path)?;
+            if existing.matches(self) {
+                // It might have been stored before some of its hashes were indexed
+                backend.ensure_indexed(&self.hashes)?;
+                if existing.annotations == self.annotations {
+                    log::debug!(
+                        "File {} is already in sync",
+                        relative_path.as_ref().display()
+                    );
+                    return Ok(Outcome::Present);
+                }
+                // Only the annotations changed, the rest describes the stored object as is
                 log::debug!(
-                    "File {} is already in sync",
+                    "Updating the annotations of {}",
                     relative_path.as_ref().display()
                 );
+                let updated = FileMetadata {
+                    annotations: self.annotations.clone(),
+                    ..existing
+                };
+                fs::write(
+                    &dvs_file_path,
+                    serde_json::to_string_pretty(&updated).expect("valid json"),
+                )?;
                 return Ok(Outcome::Present);
             }
         }
 
+        // Objects are shared by every file with the same content, so one already stored is
+        // never rewritten: other metadata files describe it as it is. The new metadata has to
+        // describe it as it is too, not as it would be stored with the current settings.
+        let metadata = if storage_exists {
+            let (compression, encrypted) = backend.stored_as(&self.hashes)?.ok_or_else(|| {
+                anyhow!(
+                    "The content of {} is already stored but can't be read back, \
+                     it may be encrypted with a key that isn't configured",
+                    relative_path.as_ref().display()
+                )
+            })?;
+            FileMetadata {
+                compression,
+                // Not recorded with the object, only informational anyway
+                compression_level: None,
+                encrypted,
+                ..self.clone()
+            }
+        } else {
+            self.clone()
+        };
+
         // We do an atomic update, either everything works or we error
         // 1. Create metadata dirs first
         if let Some(parent) = dvs_file_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        // 2. Read old storage content for rollback (if any)
-        let old_storage_content = backend.read(&self.hashes)?;
-
-        // 3. Store file to backend
-        let storage_res = backend.store(&self.hashes, source_file.as_ref(), self.compression);
-
-        // 4. Then metadata
+        // 2. Store file to backend, unless it's already there
+        let storage_res = if storage_exists {
+            Ok(())
+        } else {
+            backend.store(
+                &self.hashes,
+                source_file.as_ref(),
+                self.compression,
+                self.compression_level,
+            )
+        };
+
+        // 3. Then metadata
         let old_metadata_content = fs::read(&dvs_file_path).ok();
         log::debug!("Writing metadata to {}", dvs_file_path.display());
         let metadata_res = fs::write(
             &dvs_file_path,
-            serde_json::to_string_pretty(self).expect("valid json"),
+            serde_json::to_string_pretty(&metadata).expect("valid json"),
         );
 
         match (storage_res, metadata_res) {
             (Ok(_), Ok(_)) => {
                 let audit_entry = AuditEntry::new_add(
                     operation_id,
-                    AuditFile {
-                        path: relative_path.as_ref().to_path_buf(),
-                        hashes: self.hashes.clone(),
-                    },
+                    AuditFile::new(relative_path.as_ref(), self.hashes.clone()),
                 );
                 if let Err(e) = backend.log_audit(&audit_entry) {
                     log::error!("Failed to write audit log {audit_entry:?}: {e}");
@@ -163,9 +353,7 @@ impl FileMetadata {
                     "Metadata write failed, rolling back storage for {}",
                     relative_path.as_ref().display()
                 );
-                if let Some(old) = old_storage_content {
-                    backend.store_bytes(&self.hashes, &old)?;
-                } else {
+                if !storage_exists {
                     backend.remove(&self.hashes)?;
                 }
                 bail!("Failed to write metadata file: {dvs_file_path:?}")
@@ -180,11 +368,7 @@ impl FileMetadata {
                 } else {
                     fs::remove_file(&dvs_file_path)?;
                 }
-                if let Some(old) = old_storage_content {
-                    backend.store_bytes(&self.hashes, &old)?;
-                } else {
-                    backend.remove(&self.hashes)?;
-                }
+                backend.remove(&self.hashes)?;
                 bail!("Failed to write metadata file: {dvs_file_path:?}: {e}")
             }
         }
@@ -195,28 +379,98 @@ impl FileMetadata {
 pub struct FileStatus {
     pub path: PathBuf,
     pub status: Status,
+    /// Size of the tracked version, None for untracked files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Modification time of the tracked version when it was added, if recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
 }
 
-fn get_file_status(paths: &DvsPaths, relative_path: impl AsRef<Path>) -> Result<Status> {
-    let dvs_file_path = paths.metadata_path(relative_path.as_ref());
+fn get_file_status(
+    paths: &DvsPaths,
+    relative_path: impl AsRef<Path>,
+    progress: Option<&HashProgress>,
+) -> Result<FileStatus> {
+    let relative_path = relative_path.as_ref();
+    let dvs_file_path = paths.metadata_path(relative_path);
     if !dvs_file_path.is_file() {
-        return Ok(Status::Untracked);
+        return Ok(FileStatus {
+            path: relative_path.to_path_buf(),
+            status: Status::Untracked,
+            size: None,
+            mtime: None,
+            annotations: BTreeMap::new(),
+        });
     }
-    let existing_metadata: FileMetadata = serde_json::from_reader(fs::File::open(dvs_file_path)?)?;
+    let existing_metadata = FileMetadata::load(dvs_file_path)?;
     // If we have read the metadata, but we can't find the original file
-    let file_path = paths.file_path(relative_path.as_ref());
-    if !file_path.is_file() {
-        return Ok(Status::Absent);
-    }
-    let current_metadata = FileMetadata::from_file(&file_path, Compression::None, None)?;
-    if existing_metadata == current_metadata {
-        Ok(Status::Current)
+    let file_path = paths.file_path(relative_path);
+    let status = if !file_path.is_file() {
+        Status::Absent
     } else {
-        Ok(Status::Unsynced)
+        match existing_metadata.matches_file(&file_path, progress, &HashOptions::default()) {
+            Ok(true) => Status::Current,
+            Ok(false) => Status::Unsynced,
+            Err(e) if is_permission_denied(&e) => Status::Unreadable,
+            Err(e) => return Err(e),
+        }
+    };
+    Ok(FileStatus {
+        path: relative_path.to_path_buf(),
+        status,
+        size: Some(existing_metadata.size),
+        mtime: existing_metadata.mtime,
+        annotations: existing_metadata.annotations,
+    })
+}
+
+fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Aggregated sizes over the results of `get_status`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTotals {
+    /// Number of tracked files
+    pub files: usize,
+    /// Sum of the sizes of all tracked files
+    pub bytes: u64,
+    /// Bytes of the files whose local copy matches the tracked version
+    pub current_bytes: u64,
+    /// Bytes of the tracked files missing from the working tree
+    pub absent_bytes: u64,
+}
+
+impl StatusTotals {
+    pub fn from_statuses(statuses: &[FileStatus]) -> Self {
+        let mut totals = Self::default();
+        for file_status in statuses {
+            let Some(size) = file_status.size else {
+                continue;
+            };
+            totals.files += 1;
+            totals.bytes += size;
+            match file_status.status {
+                Status::Current => totals.current_bytes += size,
+                Status::Absent => totals.absent_bytes += size,
+                Status::Untracked | Status::Unsynced | Status::Unreadable => {}
+            }
+        }
+        totals
     }
 }
 
-pub fn get_status(paths: &DvsPaths) -> Result<Vec<FileStatus>> {
+/// Returns the status of every tracked file.
+///
+/// Enumeration is driven by the metadata folder, not the working tree: every `.dvs`
+/// file yields an entry, so a tracked file deleted locally is reported as `Absent`
+/// and files that were never added are not listed at all.
+pub fn get_status(paths: &DvsPaths, options: &StatusOptions) -> Result<Vec<FileStatus>> {
     let dvs_directory = paths.metadata_folder();
     log::debug!("Scanning metadata folder: {}", dvs_directory.display());
     let mut results = Vec::new();
@@ -234,77 +488,359 @@ pub fn get_status(paths: &DvsPaths) -> Result<Vec<FileStatus>> {
         let dvs_path = entry.path();
         // Strip dvs_directory prefix and .dvs suffix to get relative path
         let relative = dvs_path.strip_prefix(&dvs_directory)?.with_extension("");
-        let status = get_file_status(paths, &relative)?;
-        results.push(FileStatus {
-            path: relative.to_path_buf(),
-            status,
-        });
+        if options.exclude.is_match(&relative) {
+            log::debug!("Excluding {} from status", relative.display());
+            continue;
+        }
+        results.push(get_file_status(paths, &relative, options.progress)?);
     }
     log::debug!("Found {} tracked files", results.len());
     Ok(results)
 }
 
+/// Groups the paths that only differ by case. Those would overwrite each other
+/// when retrieved on a case-insensitive filesystem (macOS/Windows defaults).
+/// Only groups of at least 2 paths are returned, sorted for stable output.
+pub fn find_case_collisions<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<Vec<PathBuf>> {
+    let mut by_lowercase: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        by_lowercase
+            .entry(path.to_string_lossy().to_lowercase())
+            .or_default()
+            .push(path.to_path_buf());
+    }
+    let mut collisions: Vec<_> = by_lowercase
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    collisions.sort();
+    collisions
+}
+
+/// `status` is the status of the file if it was already computed, to avoid hashing it again
 fn get_file(
+    operation_id: Uuid,
     backend: &dyn Backend,
     paths: &DvsPaths,
     relative_path: impl AsRef<Path>,
+    status: Option<Status>,
 ) -> Result<Outcome> {
-    log::debug!("Retrieving file: {}", relative_path.as_ref().display());
-    let dvs_file_path = paths.metadata_path(relative_path.as_ref());
+    let relative_path = relative_path.as_ref();
+    let metadata = load_for_get(backend, paths, relative_path)?;
+    let target_path = paths.file_path(relative_path);
+
+    let outcome = if is_present(&target_path, &metadata, status)? {
+        Outcome::Present
+    } else {
+        retrieve_verified(backend, &metadata, relative_path, &target_path)?;
+        Outcome::Copied
+    };
+    log_get_audit(operation_id, backend, relative_path, &metadata, outcome);
+    Ok(outcome)
+}
+
+/// Loads the metadata of a tracked file, making sure its object is in storage
+fn load_for_get(
+    backend: &dyn Backend,
+    paths: &DvsPaths,
+    relative_path: &Path,
+) -> Result<FileMetadata> {
+    log::debug!("Retrieving file: {}", relative_path.display());
+    let dvs_file_path = paths.metadata_path(relative_path);
     if !dvs_file_path.is_file() {
-        bail!(
-            "File {} is not tracked by DVS",
-            relative_path.as_ref().display()
-        );
+        bail!("File {} is not tracked by DVS", relative_path.display());
     }
 
-    let metadata: FileMetadata = serde_json::from_reader(fs::File::open(&dvs_file_path)?)?;
+    let metadata = FileMetadata::load(&dvs_file_path)?;
     log::debug!(
         "Read metadata for {}: {}",
-        relative_path.as_ref().display(),
+        relative_path.display(),
         metadata.hashes
     );
 
     if !backend.exists(&metadata.hashes)? {
-        bail!("Storage file missing for hash: {}", metadata.hashes);
+        return Err(IntegrityError::new(format!(
+            "Storage file missing for hash: {}",
+            metadata.hashes
+        ))
+        .into());
     }
+    Ok(metadata)
+}
 
-    let target_path = paths.file_path(relative_path.as_ref());
-
-    // Check if target already exists and matches
-    if target_path.is_file() {
-        let current = FileMetadata::from_file(&target_path, Compression::None, None)?;
-        if current == metadata {
-            log::debug!(
-                "File {} already present locally and matches",
-                relative_path.as_ref().display()
-            );
-            return Ok(Outcome::Present);
-        }
+/// Whether the file at `target_path` already matches the metadata, only hashing it
+/// if its `status` wasn't already computed
+fn is_present(target_path: &Path, metadata: &FileMetadata, status: Option<Status>) -> Result<bool> {
+    match status {
+        Some(Status::Current) => return Ok(true),
+        Some(Status::Absent | Status::Unsynced) => return Ok(false),
+        Some(Status::Untracked | Status::Unreadable) | None => {}
+    }
+    if !target_path.is_file() {
+        return Ok(false);
+    }
+    if metadata.matches_file(target_path, None, &HashOptions::default())? {
+        log::debug!(
+            "File {} already present locally and matches",
+            target_path.display()
+        );
+        return Ok(true);
     }
+    Ok(false)
+}
 
-    // Retrieve from backend to target path
+/// Copies the object from storage to `target_path` and checks it hashes to what the metadata
+/// records, removing it otherwise
+fn retrieve_verified(
+    backend: &dyn Backend,
+    metadata: &FileMetadata,
+    relative_path: &Path,
+    target_path: &Path,
+) -> Result<()> {
     log::debug!(
         "Copying {} from storage to {}",
         metadata.hashes,
         target_path.display()
     );
     backend
-        .retrieve(&metadata.hashes, &target_path, metadata.compression)
-        .with_context(|| format!("Failed to retrieve {}", relative_path.as_ref().display()))?;
-    let actual = FileMetadata::from_file(&target_path, Compression::None, None)?;
-    if actual.hashes != metadata.hashes {
-        fs::remove_file(&target_path)?;
-        bail!("Retrieved file does not match expected hash");
+        .retrieve(
+            &metadata.hashes,
+            target_path,
+            metadata.compression,
+            metadata.encrypted,
+        )
+        .with_context(|| format!("Failed to retrieve {}", relative_path.display()))?;
+    if !metadata.matches_file(target_path, None, &HashOptions::default())? {
+        fs::remove_file(target_path)?;
+        return Err(IntegrityError::new(format!(
+            "Retrieved file {} does not match expected hash",
+            relative_path.display()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// A file retrieved to the staging area, waiting to be moved to the working tree
+struct StagedFile {
+    relative_path: PathBuf,
+    metadata: FileMetadata,
+    /// `None` when the file was already present and doesn't need to be moved
+    staged_path: Option<PathBuf>,
+}
+
+/// Atomic get retrieves `name` as `.{name}.dvs-staging-{operation_id}` next to it, so moving it
+/// in place is a rename within the same filesystem. The file it replaces is set aside with an
+/// extra `.orig`. Both are ignored by git in case a crash leaves them behind, with this
+/// pattern added to the root `.gitignore` by `init`.
+pub(crate) const STAGING_GITIGNORE_PATTERN: &str = ".*.dvs-staging-*";
+
+fn staging_path(target: &Path, operation_id: Uuid) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{name}.dvs-staging-{operation_id}"))
+}
+
+fn backup_path(target: &Path, operation_id: Uuid) -> PathBuf {
+    let mut backup = staging_path(target, operation_id).into_os_string();
+    backup.push(".orig");
+    PathBuf::from(backup)
+}
+
+/// Retrieves every file next to its target and only moves them in place once they have all
+/// been retrieved and verified.
+/// If anything fails, the working tree is left as it was.
+fn get_files_atomic(
+    operation_id: Uuid,
+    backend: &dyn Backend,
+    paths: &DvsPaths,
+    files: Vec<(PathBuf, Option<Status>)>,
+) -> Result<Vec<GetResult>> {
+    let mut staged = Vec::with_capacity(files.len());
+    // Folders created to stage files in, to remove if we roll back
+    let mut created_dirs = Vec::new();
+    let result = stage_files(
+        operation_id,
+        backend,
+        paths,
+        files,
+        &mut staged,
+        &mut created_dirs,
+    )
+    .and_then(|_| promote_staged(operation_id, paths, &staged));
+
+    // Whatever was not moved in place, because something failed, is removed
+    for staged_path in staged.iter().filter_map(|file| file.staged_path.as_ref()) {
+        if staged_path.exists() {
+            if let Err(e) = fs::remove_file(staged_path) {
+                log::error!("Failed to remove {}: {e}", staged_path.display());
+            }
+        }
+    }
+    if result.is_err() {
+        // Deepest first, and only if they are still empty
+        for dir in created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    result.context("Atomic get failed, the working tree was left unchanged")?;
+
+    let mut results = Vec::with_capacity(staged.len());
+    for file in staged {
+        let outcome = if file.staged_path.is_some() {
+            Outcome::Copied
+        } else {
+            Outcome::Present
+        };
+        log_get_audit(
+            operation_id,
+            backend,
+            &file.relative_path,
+            &file.metadata,
+            outcome,
+        );
+        results.push(GetResult {
+            path: file.relative_path,
+            outcome,
+        });
+    }
+    Ok(results)
+}
+
+/// Retrieves the files that aren't present next to their target, pushing them to `staged` as
+/// they are so they can be cleaned up if a later one fails
+fn stage_files(
+    operation_id: Uuid,
+    backend: &dyn Backend,
+    paths: &DvsPaths,
+    files: Vec<(PathBuf, Option<Status>)>,
+    staged: &mut Vec<StagedFile>,
+    created_dirs: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for (relative_path, status) in files {
+        let metadata = load_for_get(backend, paths, &relative_path)?;
+        let target = paths.file_path(&relative_path);
+        if is_present(&target, &metadata, status)? {
+            staged.push(StagedFile {
+                relative_path,
+                metadata,
+                staged_path: None,
+            });
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            create_dirs(parent, created_dirs)?;
+        }
+        let staged_path = staging_path(&target, operation_id);
+        staged.push(StagedFile {
+            relative_path,
+            metadata,
+            staged_path: Some(staged_path.clone()),
+        });
+        let file = staged.last().expect("just pushed");
+        retrieve_verified(backend, &file.metadata, &file.relative_path, &staged_path)?;
+    }
+    Ok(())
+}
+
+/// Creates `dir` and its missing parents, recording the ones created from the top down
+fn create_dirs(dir: &Path, created: &mut Vec<PathBuf>) -> Result<()> {
+    let missing: Vec<_> = dir
+        .ancestors()
+        .take_while(|ancestor| !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    for dir in missing.into_iter().rev() {
+        fs::create_dir(&dir)?;
+        created.push(dir);
+    }
+    Ok(())
+}
+
+/// Moves the staged files in place. Files they replace are set aside next to them
+/// so they can be put back if a move fails, and removed once all are moved.
+fn promote_staged(operation_id: Uuid, paths: &DvsPaths, staged: &[StagedFile]) -> Result<()> {
+    // (target, backup of what was there before)
+    let mut promoted: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+    for file in staged {
+        let Some(staged_path) = &file.staged_path else {
+            continue;
+        };
+        let target = paths.file_path(&file.relative_path);
+        let backup_path = backup_path(&target, operation_id);
+        if let Err(e) = promote_file(staged_path, target, &backup_path, &mut promoted) {
+            for (target, backup) in promoted.into_iter().rev() {
+                let restored = match backup {
+                    Some(backup) => fs::rename(&backup, &target),
+                    None if target.exists() => fs::remove_file(&target),
+                    None => Ok(()),
+                };
+                if let Err(e) = restored {
+                    log::error!("Failed to restore {}: {e}", target.display());
+                }
+            }
+            return Err(e);
+        }
+    }
+    for backup in promoted.into_iter().filter_map(|(_, backup)| backup) {
+        if let Err(e) = fs::remove_file(&backup) {
+            log::error!("Failed to remove {}: {e}", backup.display());
+        }
+    }
+    Ok(())
+}
+
+/// Records the move in `promoted` before doing it so a partial move can be undone
+fn promote_file(
+    staged_path: &Path,
+    target: PathBuf,
+    backup_path: &Path,
+    promoted: &mut Vec<(PathBuf, Option<PathBuf>)>,
+) -> Result<()> {
+    let backup = if target.exists() {
+        fs::rename(&target, backup_path)?;
+        Some(backup_path.to_path_buf())
+    } else {
+        None
+    };
+    promoted.push((target.clone(), backup));
+    fs::rename(staged_path, &target)?;
+    Ok(())
+}
+
+/// Failing to write the audit log should not fail the get itself
+fn log_get_audit(
+    operation_id: Uuid,
+    backend: &dyn Backend,
+    relative_path: &Path,
+    metadata: &FileMetadata,
+    outcome: Outcome,
+) {
+    let audit_entry = AuditEntry::new_get(
+        operation_id,
+        AuditFile::new(relative_path, metadata.hashes.clone()),
+        outcome,
+    );
+    if let Err(e) = backend.log_audit(&audit_entry) {
+        log::error!("Failed to write audit log {audit_entry:?}: {e}");
     }
-    Ok(Outcome::Copied)
 }
 
 /// Result of adding a single file.
+/// Either `outcome` or `error` is set, the latter only when adding with `keep_going`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddResult {
     pub path: PathBuf,
-    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Possible problems with the file that didn't prevent adding it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 /// Result of getting a single file.
@@ -314,6 +850,62 @@ pub struct GetResult {
     pub outcome: Outcome,
 }
 
+/// Result of checking the stored object of a tracked file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyOutcome {
+    /// Stored object is present and matches the metadata.
+    Ok,
+    /// Stored object does not hash to what the metadata records.
+    Corrupt,
+    /// Stored object is not in the storage.
+    Missing,
+}
+
+/// Result of verifying a single file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub path: PathBuf,
+    pub outcome: VerifyOutcome,
+}
+
+/// Checks that the stored objects of the given tracked files are retrievable and intact,
+/// without writing anything to the working tree.
+pub fn verify_files(
+    files: Vec<PathBuf>,
+    paths: &DvsPaths,
+    backend: &dyn Backend,
+) -> Result<Vec<VerifyResult>> {
+    let matched_paths = paths.validate_for_get(&files);
+    let missing: Vec<_> = matched_paths
+        .iter()
+        .filter(|(_, exists)| !*exists)
+        .map(|(p, _)| p.display().to_string())
+        .collect();
+    if !missing.is_empty() {
+        bail!("The following files were not found: {}", missing.join(", "));
+    }
+
+    let mut results = Vec::new();
+    for (relative_path, _) in matched_paths {
+        let metadata = FileMetadata::load(paths.metadata_path(&relative_path))?;
+        let outcome = if !backend.exists(&metadata.hashes)? {
+            VerifyOutcome::Missing
+        } else if backend.verify(&metadata.hashes, metadata.compression, metadata.encrypted)? {
+            VerifyOutcome::Ok
+        } else {
+            VerifyOutcome::Corrupt
+        };
+        log::info!("Verified {} ({:?})", relative_path.display(), outcome);
+        results.push(VerifyResult {
+            path: relative_path,
+            outcome,
+        });
+    }
+
+    Ok(results)
+}
+
 /// Adds files matching a glob pattern to DVS.
 ///
 /// The pattern is matched against files relative to cwd.
@@ -322,42 +914,216 @@ pub fn add_files(
     files: Vec<PathBuf>,
     paths: &DvsPaths,
     backend: &dyn Backend,
-    message: Option<String>,
-    compression: Compression,
+    options: &AddOptions,
 ) -> Result<Vec<AddResult>> {
+    // Every file is checked before adding anything. Files failing a check stop the whole add,
+    // or with `keep_going` are reported with their own error while the others are added.
+    // Only the first problem found with a file is reported.
+    let mut rejected: HashMap<PathBuf, String> = HashMap::new();
+    let reject = |rejected: &mut HashMap<PathBuf, String>, failed: Vec<(PathBuf, String)>| {
+        for (path, error) in failed {
+            log::error!("Failed to add {}: {error}", path.display());
+            rejected.entry(path).or_insert(error);
+        }
+    };
+
+    let special: Vec<_> = files
+        .iter()
+        .filter_map(|f| Some((f, special_file_kind(&paths.file_path(f))?)))
+        .collect();
+    if !special.is_empty() {
+        if !options.keep_going {
+            let special: Vec<_> = special
+                .iter()
+                .map(|(f, kind)| format!("{} ({kind})", f.display()))
+                .collect();
+            bail!(
+                "Only regular files can be added, the following are not: {}",
+                special.join(", ")
+            );
+        }
+        let failed = special
+            .into_iter()
+            .map(|(f, kind)| {
+                let error = format!(
+                    "Only regular files can be added, {} is a {kind}",
+                    f.display()
+                );
+                (f.clone(), error)
+            })
+            .collect();
+        reject(&mut rejected, failed);
+    }
+
     let matched_paths = paths.validate_for_add(&files);
     let missing: Vec<_> = matched_paths
         .iter()
-        .filter(|(_, exists)| !*exists)
-        .map(|(p, _)| p.display().to_string())
+        .filter(|(p, exists)| !*exists && !rejected.contains_key(p))
+        .map(|(p, _)| p)
         .collect();
     if !missing.is_empty() {
-        bail!("The following files were not found: {}", missing.join(", "));
+        if !options.keep_going {
+            let missing: Vec<_> = missing.iter().map(|p| p.display().to_string()).collect();
+            bail!("The following files were not found: {}", missing.join(", "));
+        }
+        let failed = missing
+            .into_iter()
+            .map(|p| (p.clone(), format!("{} was not found", p.display())))
+            .collect();
+        reject(&mut rejected, failed);
+    }
+
+    let skip =
+        |relative_path: &Path| options.if_absent && paths.metadata_path(relative_path).is_file();
+    let to_check: Vec<_> = matched_paths
+        .iter()
+        .map(|(p, _)| p)
+        .filter(|p| !skip(p) && !rejected.contains_key(*p))
+        .collect();
+
+    // Empty files are usually the output of a failed step
+    let mut empty = Vec::new();
+    for relative_path in &to_check {
+        if fs::metadata(paths.file_path(relative_path))?.len() == 0 {
+            empty.push(*relative_path);
+        }
+    }
+    if !empty.is_empty() {
+        let empty_list: Vec<_> = empty.iter().map(|p| p.display().to_string()).collect();
+        if options.allow_empty {
+            log::warn!("Adding empty files: {}", empty_list.join(", "));
+        } else if !options.keep_going {
+            bail!(
+                "The following files are empty, use --allow-empty to add them anyway: {}",
+                empty_list.join(", ")
+            );
+        } else {
+            let failed = empty
+                .into_iter()
+                .map(|p| {
+                    let error = format!(
+                        "{} is empty, use --allow-empty to add it anyway",
+                        p.display()
+                    );
+                    (p.clone(), error)
+                })
+                .collect();
+            reject(&mut rejected, failed);
+        }
+    }
+
+    // The user's validator can catch truncated files we can't tell apart
+    if let Some(command) = &options.validate_command {
+        let mut failed = Vec::new();
+        for relative_path in &to_check {
+            if rejected.contains_key(*relative_path) {
+                continue;
+            }
+            if let Err(e) = run_validator(command, &paths.file_path(relative_path)) {
+                failed.push(((*relative_path).clone(), format!("{e:#}")));
+            }
+        }
+        if !failed.is_empty() && !options.keep_going {
+            let errors: Vec<_> = failed.into_iter().map(|(_, e)| e).collect();
+            bail!("{}", errors.join("\n"));
+        }
+        reject(&mut rejected, failed);
     }
 
     let mut results = Vec::new();
     let operation_id = Uuid::new_v4();
 
     for (relative_path, _) in matched_paths {
+        if let Some(error) = rejected.remove(&relative_path) {
+            results.push(AddResult {
+                path: relative_path,
+                outcome: None,
+                error: Some(error),
+                warnings: Vec::new(),
+            });
+            continue;
+        }
+        if skip(&relative_path) {
+            log::info!("Skipping already tracked {}", relative_path.display());
+            results.push(AddResult {
+                path: relative_path,
+                outcome: Some(Outcome::Skipped),
+                error: None,
+                warnings: Vec::new(),
+            });
+            continue;
+        }
         let full_path = paths.file_path(&relative_path);
 
-        let metadata = FileMetadata::from_file(&full_path, compression, message.clone())?;
-        let outcome = metadata.save(operation_id, &full_path, backend, paths, &relative_path)?;
-        log::info!(
-            "Successfully added {} ({:?})",
-            relative_path.display(),
-            outcome
-        );
-        results.push(AddResult {
-            path: relative_path,
-            outcome,
+        // A text file that looks binary usually means a pipeline step compressed it unexpectedly
+        let mut warnings = Vec::new();
+        match sniff_binary_text_file(&full_path, &options.text_extensions) {
+            Ok(Some(description)) => {
+                let warning = format!(
+                    "{} is expected to be text but looks like {description}",
+                    relative_path.display()
+                );
+                log::warn!("{warning}");
+                warnings.push(warning);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!(
+                "Failed to check the content of {}: {e}",
+                relative_path.display()
+            ),
+        }
+
+        let added = FileMetadata::from_file_with_progress(
+            &full_path,
+            options.compression,
+            options.message.clone(),
+            options.progress,
+            &options.hashing,
+        )
+        .and_then(|mut metadata| {
+            // Annotations accumulate over adds, the new values replacing the old ones
+            let metadata_path = paths.metadata_path(&relative_path);
+            if metadata_path.is_file() {
+                metadata.annotations = FileMetadata::load(&metadata_path)?.annotations;
+            }
+            metadata.annotations.extend(options.annotations.clone());
+            metadata.compression_level = options.compression_level;
+            metadata.encrypted = backend.encrypts();
+            metadata.save(operation_id, &full_path, backend, paths, &relative_path)
         });
+        match added {
+            Ok(outcome) => {
+                log::info!(
+                    "Successfully added {} ({:?})",
+                    relative_path.display(),
+                    outcome
+                );
+                results.push(AddResult {
+                    path: relative_path,
+                    outcome: Some(outcome),
+                    error: None,
+                    warnings,
+                });
+            }
+            Err(e) if options.keep_going => {
+                log::error!("Failed to add {}: {e:#}", relative_path.display());
+                results.push(AddResult {
+                    path: relative_path,
+                    outcome: None,
+                    error: Some(format!("{e:#}")),
+                    warnings,
+                });
+            }
+            Err(e) => return Err(e),
+        }
     }
 
-    if let Err(e) = add_to_gitignore(
-        paths.repo_root(),
-        &results.iter().map(|r| r.path.clone()).collect::<Vec<_>>(),
-    ) {
+    let added: Vec<_> = results
+        .iter()
+        .filter(|r| r.error.is_none())
+        .map(|r| r.path.clone())
+        .collect();
+    if let Err(e) = add_to_gitignore(paths.repo_root(), &added) {
         log::warn!("Failed to update .gitignore: {e}");
     }
 
@@ -372,6 +1138,7 @@ pub fn get_files(
     files: Vec<PathBuf>,
     paths: &DvsPaths,
     backend: &dyn Backend,
+    options: &GetOptions,
 ) -> Result<Vec<GetResult>> {
     let matched_paths = paths.validate_for_get(&files);
     let missing: Vec<_> = matched_paths
@@ -383,10 +1150,36 @@ pub fn get_files(
         bail!("The following files were not found: {}", missing.join(", "));
     }
 
-    let mut results = Vec::new();
-
+    // Check them all before retrieving anything so we never lose local changes.
+    // The statuses are kept so files aren't hashed again when retrieving them.
+    let mut files = Vec::with_capacity(matched_paths.len());
+    let mut modified = Vec::new();
     for (relative_path, _) in matched_paths {
-        let outcome = get_file(backend, paths, &relative_path)?;
+        let status = if options.overwrite {
+            None
+        } else {
+            Some(get_file_status(paths, &relative_path, None)?.status)
+        };
+        if status == Some(Status::Unsynced) {
+            modified.push(relative_path.display().to_string());
+        }
+        files.push((relative_path, status));
+    }
+    if !modified.is_empty() {
+        bail!(
+            "The following files have local changes, use --overwrite to replace them: {}",
+            modified.join(", ")
+        );
+    }
+
+    let operation_id = Uuid::new_v4();
+    if options.atomic {
+        return get_files_atomic(operation_id, backend, paths, files);
+    }
+
+    let mut results = Vec::new();
+    for (relative_path, status) in files {
+        let outcome = get_file(operation_id, backend, paths, &relative_path, status)?;
         log::info!(
             "Successfully retrieved {} ({:?})",
             relative_path.display(),
@@ -401,10 +1194,35 @@ pub fn get_files(
     Ok(results)
 }
 
+/// Runs the user's validator on `path`: `command` is the program and its arguments,
+/// the path is appended to them. A non-zero exit rejects the file.
+fn run_validator(command: &[String], path: &Path) -> Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        return Ok(());
+    };
+    log::debug!("Validating {} with {program}", path.display());
+    let output = std::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .output()
+        .with_context(|| format!("Failed to run the validator {program}"))?;
+    if !output.status.success() {
+        bail!(
+            "{} was rejected by the validator ({}): {}",
+            path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::testutil::{create_file, create_temp_git_repo, init_dvs_repo};
+    use crate::audit::Action;
+    use crate::hashes::HashAlg;
+    use crate::testutil::{create_file, create_temp_git_repo, init_dvs_repo, seed_tracked};
 
     fn make_paths(root: &Path, config: &crate::config::Config) -> DvsPaths {
         DvsPaths::new(
@@ -432,6 +1250,42 @@ mod tests {
         assert_eq!(metadata.message, Some("test message".to_string()));
     }
 
+    #[test]
+    fn file_metadata_reports_progress_for_large_files_only() {
+        let (_tmp, root) = create_temp_git_repo();
+        let large = create_file(&root, "large.bin", &[7; 4096]);
+        let tiny = create_file(&root, "tiny.txt", b"tiny");
+
+        let calls = std::cell::RefCell::new(Vec::new());
+        let callback = |path: &Path, done: u64, total: u64| {
+            calls.borrow_mut().push((path.to_path_buf(), done, total));
+        };
+        let progress = HashProgress {
+            min_size: 1024,
+            callback: &callback,
+        };
+
+        FileMetadata::from_file_with_progress(
+            &tiny,
+            Compression::None,
+            None,
+            Some(&progress),
+            &HashOptions::default(),
+        )
+        .unwrap();
+        assert!(calls.borrow().is_empty());
+
+        FileMetadata::from_file_with_progress(
+            &large,
+            Compression::None,
+            None,
+            Some(&progress),
+            &HashOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(calls.borrow().last(), Some(&(large, 4096, 4096)));
+    }
+
     #[test]
     fn file_metadata_from_nonexistent_file_fails() {
         let tmp = tempfile::tempdir().unwrap();
@@ -460,12 +1314,36 @@ mod tests {
     }
 
     #[test]
-    fn save_local_returns_present_when_already_stored() {
+    fn add_creates_metadata_tree_for_new_deep_subdirectory() {
         let (_tmp, root) = create_temp_git_repo();
-        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let (config, dvs_dir) = init_dvs_repo(&root);
         let backend = config.backend();
         let paths = make_paths(&root, &config);
-        let file_path = create_file(&root, "data.bin", b"binary data");
+        create_file(&root, "a/b/c/d/deep.csv", b"deep");
+        assert!(!dvs_dir.join("a").exists());
+
+        add_files(
+            vec!["a/b/c/d/deep.csv".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        assert!(dvs_dir.join("a/b/c/d/deep.csv.dvs").is_file());
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].path, PathBuf::from("a/b/c/d/deep.csv"));
+        assert_eq!(statuses[0].status, Status::Current);
+    }
+
+    #[test]
+    fn save_local_returns_present_when_already_stored() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let file_path = create_file(&root, "data.bin", b"binary data");
 
         let metadata = FileMetadata::from_file(&file_path, Compression::Zstd, None).unwrap();
         metadata
@@ -479,6 +1357,33 @@ mod tests {
         assert_eq!(outcome, Outcome::Present);
     }
 
+    #[test]
+    fn save_present_backfills_the_index() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let file_path = create_file(&root, "data.bin", b"binary data");
+
+        let metadata = FileMetadata::from_file(&file_path, Compression::Zstd, None).unwrap();
+        metadata
+            .save(Uuid::new_v4(), &file_path, backend, &paths, "data.bin")
+            .unwrap();
+        // As if it was stored before the index existed
+        fs::remove_dir_all(root.join(".storage").join("index")).unwrap();
+        let xxh128 = metadata.hashes.xxh128.clone().unwrap();
+        assert_eq!(backend.lookup(HashAlg::Xxh128, &xxh128).unwrap(), None);
+
+        let outcome = metadata
+            .save(Uuid::new_v4(), &file_path, backend, &paths, "data.bin")
+            .unwrap();
+        assert_eq!(outcome, Outcome::Present);
+        assert_eq!(
+            backend.lookup(HashAlg::Xxh128, &xxh128).unwrap(),
+            Some(metadata.hashes.clone())
+        );
+    }
+
     #[test]
     fn get_file_status_returns_untracked_for_new_file() {
         let (_tmp, root) = create_temp_git_repo();
@@ -486,7 +1391,7 @@ mod tests {
         let paths = make_paths(&root, &config);
         create_file(&root, "new.txt", b"content");
 
-        let status = get_file_status(&paths, "new.txt").unwrap();
+        let status = get_file_status(&paths, "new.txt", None).unwrap().status;
         assert_eq!(status, Status::Untracked);
     }
 
@@ -503,7 +1408,7 @@ mod tests {
             .save(Uuid::new_v4(), &file_path, backend, &paths, "synced.txt")
             .unwrap();
 
-        let status = get_file_status(&paths, "synced.txt").unwrap();
+        let status = get_file_status(&paths, "synced.txt", None).unwrap().status;
         assert_eq!(status, Status::Current);
     }
 
@@ -523,10 +1428,31 @@ mod tests {
         // Delete the original file
         fs::remove_file(&file_path).unwrap();
 
-        let status = get_file_status(&paths, "deleted.txt").unwrap();
+        let status = get_file_status(&paths, "deleted.txt", None).unwrap().status;
         assert_eq!(status, Status::Absent);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn get_file_status_returns_unreadable_without_read_permission() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let paths = make_paths(&root, &config);
+        seed_tracked(&root, &config, &[("locked.csv", b"secret")]);
+        let file_path = root.join("locked.csv");
+        fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        // Root can read anything, there is nothing to check
+        if fs::read(&file_path).is_ok() {
+            return;
+        }
+
+        let status = get_file_status(&paths, "locked.csv", None).unwrap().status;
+        fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(status, Status::Unreadable);
+    }
+
     #[test]
     fn get_file_status_returns_unsynced_when_file_modified() {
         let (_tmp, root) = create_temp_git_repo();
@@ -543,7 +1469,9 @@ mod tests {
         // Modify the file
         fs::write(&file_path, b"changed content").unwrap();
 
-        let status = get_file_status(&paths, "modified.txt").unwrap();
+        let status = get_file_status(&paths, "modified.txt", None)
+            .unwrap()
+            .status;
         assert_eq!(status, Status::Unsynced);
     }
 
@@ -565,7 +1493,7 @@ mod tests {
         assert!(!file_path.exists());
 
         // Retrieve it
-        let outcome = get_file(backend, &paths, "retrieve.txt").unwrap();
+        let outcome = get_file(Uuid::new_v4(), backend, &paths, "retrieve.txt", None).unwrap();
         assert_eq!(outcome, Outcome::Copied);
         assert!(file_path.exists());
         assert_eq!(fs::read(&file_path).unwrap(), b"stored content");
@@ -585,8 +1513,29 @@ mod tests {
             .unwrap();
 
         // File still exists and matches - should return Present
-        let outcome = get_file(backend, &paths, "present.txt").unwrap();
+        let outcome = get_file(Uuid::new_v4(), backend, &paths, "present.txt", None).unwrap();
+        assert_eq!(outcome, Outcome::Present);
+    }
+
+    #[test]
+    fn get_file_reuses_the_known_status() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        seed_tracked(&root, &config, &[("data.txt", b"content")]);
+
+        // Same size so only hashing would notice, which a known status skips
+        fs::write(root.join("data.txt"), b"CONTENT").unwrap();
+        let status = Some(Status::Current);
+        let outcome = get_file(Uuid::new_v4(), backend, &paths, "data.txt", status).unwrap();
         assert_eq!(outcome, Outcome::Present);
+        assert_eq!(fs::read(root.join("data.txt")).unwrap(), b"CONTENT");
+
+        let status = Some(Status::Unsynced);
+        let outcome = get_file(Uuid::new_v4(), backend, &paths, "data.txt", status).unwrap();
+        assert_eq!(outcome, Outcome::Copied);
+        assert_eq!(fs::read(root.join("data.txt")).unwrap(), b"content");
     }
 
     #[test]
@@ -596,7 +1545,7 @@ mod tests {
         let backend = config.backend();
         let paths = make_paths(&root, &config);
 
-        let result = get_file(backend, &paths, "untracked.txt");
+        let result = get_file(Uuid::new_v4(), backend, &paths, "untracked.txt", None);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not tracked"));
     }
@@ -617,7 +1566,7 @@ mod tests {
                 .unwrap();
         }
 
-        let statuses = get_status(&paths).unwrap();
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
         assert_eq!(statuses.len(), 3);
 
         // All should be Current
@@ -626,6 +1575,660 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_status_reports_deleted_tracked_file_as_absent() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "kept.txt", b"kept");
+        create_file(&root, "deleted.txt", b"deleted");
+        create_file(&root, "untracked.txt", b"untracked");
+
+        add_files(
+            vec!["kept.txt".into(), "deleted.txt".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        fs::remove_file(root.join("deleted.txt")).unwrap();
+
+        let mut statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        statuses.sort_by(|a, b| a.path.cmp(&b.path));
+        let statuses: Vec<_> = statuses
+            .iter()
+            .map(|s| (s.path.clone(), s.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (PathBuf::from("deleted.txt"), Status::Absent),
+                (PathBuf::from("kept.txt"), Status::Current),
+            ]
+        );
+    }
+
+    #[test]
+    fn status_totals_sum_tracked_sizes() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "a.bin", &[0; 100]);
+        create_file(&root, "b.bin", &[1; 250]);
+        create_file(&root, "c.bin", &[2; 40]);
+        add_files(
+            vec!["a.bin".into(), "b.bin".into(), "c.bin".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        fs::remove_file(root.join("b.bin")).unwrap();
+        fs::write(root.join("c.bin"), b"modified").unwrap();
+
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        let totals = StatusTotals::from_statuses(&statuses);
+        assert_eq!(
+            totals,
+            StatusTotals {
+                files: 3,
+                bytes: 390,
+                current_bytes: 100,
+                absent_bytes: 250,
+            }
+        );
+    }
+
+    #[test]
+    fn find_case_collisions_groups_case_variants() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        // Works on a case-sensitive filesystem, simulating two users on Linux
+        // tracking paths that would clash for a third one on macOS
+        let files = ["Data/a.csv", "data/A.csv", "data/b.csv", "readme.md"];
+        for name in files {
+            create_file(&root, name, name.as_bytes());
+        }
+        if fs::read(root.join("Data/a.csv")).unwrap() != b"Data/a.csv" {
+            // The filesystem itself is case-insensitive, we can't create the fixture
+            return;
+        }
+        add_files(
+            files.iter().map(PathBuf::from).collect(),
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        let collisions = find_case_collisions(statuses.iter().map(|s| s.path.as_path()));
+        assert_eq!(
+            collisions,
+            vec![vec![
+                PathBuf::from("Data/a.csv"),
+                PathBuf::from("data/A.csv")
+            ]]
+        );
+    }
+
+    #[test]
+    fn get_status_skips_excluded_paths() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let files = ["keep.txt", "tmp/a.txt", "tmp/nested/b.txt", "data/tmp.txt"];
+        for name in files {
+            create_file(&root, name, name.as_bytes());
+        }
+        add_files(
+            files.iter().map(PathBuf::from).collect(),
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        let exclude = globset::GlobSetBuilder::new()
+            .add(globset::Glob::new("tmp/**").unwrap())
+            .build()
+            .unwrap();
+        let options = StatusOptions {
+            exclude,
+            ..Default::default()
+        };
+        let mut tracked: Vec<_> = get_status(&paths, &options)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        tracked.sort();
+        assert_eq!(
+            tracked,
+            vec![PathBuf::from("data/tmp.txt"), PathBuf::from("keep.txt")]
+        );
+    }
+
+    #[test]
+    fn add_files_records_annotations() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "data.csv", b"a,b\n1,2\n");
+
+        let annotations = BTreeMap::from([
+            (
+                "source".to_string(),
+                "https://example.com/data.csv".to_string(),
+            ),
+            ("version".to_string(), "2".to_string()),
+        ]);
+        add_files(
+            vec!["data.csv".into()],
+            &paths,
+            backend,
+            &AddOptions {
+                annotations: annotations.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].status, Status::Current);
+        assert_eq!(statuses[0].annotations, annotations);
+
+        // Re-adding the unchanged file updates its annotations
+        let results = add_files(
+            vec!["data.csv".into()],
+            &paths,
+            backend,
+            &AddOptions {
+                annotations: BTreeMap::from([
+                    ("version".to_string(), "3".to_string()),
+                    ("owner".to_string(), "data-team".to_string()),
+                ]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(results[0].outcome, Some(Outcome::Present));
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert_eq!(statuses[0].status, Status::Current);
+        assert_eq!(
+            statuses[0].annotations,
+            BTreeMap::from([
+                ("owner".to_string(), "data-team".to_string()),
+                (
+                    "source".to_string(),
+                    "https://example.com/data.csv".to_string()
+                ),
+                ("version".to_string(), "3".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn add_files_compresses_at_the_requested_level() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let content = b"a,b\n1,2\n".repeat(1000);
+        create_file(&root, "data.csv", &content);
+
+        add_files(
+            vec!["data.csv".into()],
+            &paths,
+            backend,
+            &AddOptions {
+                compression: Compression::Zstd,
+                compression_level: Some(19),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let metadata = FileMetadata::load(paths.metadata_path(Path::new("data.csv"))).unwrap();
+        assert_eq!(metadata.compression_level, Some(19));
+        let stored = backend.read(&metadata.hashes).unwrap().unwrap();
+        assert!(stored.len() < content.len());
+        assert_eq!(zstd::decode_all(stored.as_slice()).unwrap(), content);
+
+        fs::remove_file(root.join("data.csv")).unwrap();
+        get_files(
+            vec!["data.csv".into()],
+            &paths,
+            backend,
+            &GetOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read(root.join("data.csv")).unwrap(), content);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_objects_differ_on_disk_but_get_restores_them() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (mut config, _dvs_dir) = init_dvs_repo(&root);
+        config
+            .set_encryption_key("00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff")
+            .unwrap();
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let content = b"id,secret\n1,hunter2\n".to_vec();
+        create_file(&root, "secret.csv", &content);
+
+        add_files(
+            vec!["secret.csv".into()],
+            &paths,
+            backend,
+            &AddOptions {
+                compression: Compression::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let metadata = FileMetadata::load(paths.metadata_path(Path::new("secret.csv"))).unwrap();
+        assert_eq!(metadata.hashes, Hashes::from(content.clone()));
+        let stored = backend.read(&metadata.hashes).unwrap().unwrap();
+        assert_ne!(stored, content);
+        assert!(!stored.windows(7).any(|w| w == b"hunter2"));
+        assert!(metadata.encrypted);
+        assert!(
+            backend
+                .verify(&metadata.hashes, Compression::None, true)
+                .unwrap()
+        );
+
+        fs::remove_file(root.join("secret.csv")).unwrap();
+        get_files(
+            vec!["secret.csv".into()],
+            &paths,
+            backend,
+            &GetOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read(root.join("secret.csv")).unwrap(), content);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn enabling_encryption_keeps_objects_already_stored_readable() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (mut config, dvs_dir) = init_dvs_repo(&root);
+        let paths = make_paths(&root, &config);
+        create_file(&root, "a.csv", b"same content");
+        create_file(&root, "b.csv", b"same content");
+        add_files(
+            vec!["a.csv".into()],
+            &paths,
+            config.backend(),
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        config
+            .set_encryption_key("00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff")
+            .unwrap();
+        add_files(
+            vec!["b.csv".into()],
+            &paths,
+            config.backend(),
+            &AddOptions::default(),
+        )
+        .unwrap();
+        // b.csv shares the plaintext object a.csv was stored as
+        let b = FileMetadata::load(dvs_dir.join("b.csv.dvs")).unwrap();
+        assert!(!b.encrypted);
+
+        fs::remove_file(root.join("a.csv")).unwrap();
+        fs::remove_file(root.join("b.csv")).unwrap();
+        let files: Vec<PathBuf> = vec!["a.csv".into(), "b.csv".into()];
+        get_files(files, &paths, config.backend(), &GetOptions::default()).unwrap();
+        assert_eq!(fs::read(root.join("a.csv")).unwrap(), b"same content");
+        assert_eq!(fs::read(root.join("b.csv")).unwrap(), b"same content");
+    }
+
+    #[test]
+    fn changing_compression_keeps_objects_already_stored_readable() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "a.csv", b"same content");
+        create_file(&root, "b.csv", b"same content");
+        add_files(
+            vec!["a.csv".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        let options = AddOptions {
+            compression: Compression::None,
+            ..Default::default()
+        };
+        add_files(vec!["b.csv".into()], &paths, backend, &options).unwrap();
+        let b = FileMetadata::load(dvs_dir.join("b.csv.dvs")).unwrap();
+        assert_eq!(b.compression, Compression::Zstd);
+
+        fs::remove_file(root.join("a.csv")).unwrap();
+        fs::remove_file(root.join("b.csv")).unwrap();
+        let files: Vec<PathBuf> = vec!["a.csv".into(), "b.csv".into()];
+        get_files(files, &paths, backend, &GetOptions::default()).unwrap();
+        assert_eq!(fs::read(root.join("a.csv")).unwrap(), b"same content");
+        assert_eq!(fs::read(root.join("b.csv")).unwrap(), b"same content");
+    }
+
+    #[test]
+    fn add_files_rejects_empty_files_unless_allowed() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "empty.csv", b"");
+        create_file(&root, "full.csv", b"a,b");
+        let files: Vec<PathBuf> = vec!["full.csv".into(), "empty.csv".into()];
+
+        let err = add_files(files.clone(), &paths, backend, &AddOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("empty.csv"));
+        // Nothing was added
+        assert!(!dvs_dir.join("full.csv.dvs").exists());
+
+        let options = AddOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let results = add_files(files, &paths, backend, &options).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(dvs_dir.join("empty.csv.dvs").is_file());
+    }
+
+    #[test]
+    fn empty_files_share_one_object_and_retrieve_as_empty() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "first.csv", b"");
+        create_file(&root, "nested/second.csv", b"");
+        let files: Vec<PathBuf> = vec!["first.csv".into(), "nested/second.csv".into()];
+        let options = AddOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+
+        add_files(files.clone(), &paths, backend, &options).unwrap();
+
+        let first = FileMetadata::load(paths.metadata_path(Path::new("first.csv"))).unwrap();
+        let second =
+            FileMetadata::load(paths.metadata_path(Path::new("nested/second.csv"))).unwrap();
+        assert_eq!(first.hashes, second.hashes);
+        assert_eq!(first.size, 0);
+        let object_dir = root.join(".storage").join(&first.hashes.blake3[..2]);
+        assert_eq!(fs::read_dir(object_dir).unwrap().count(), 1);
+
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert!(statuses.iter().all(|s| s.size == Some(0)));
+
+        fs::remove_file(root.join("first.csv")).unwrap();
+        fs::remove_file(root.join("nested/second.csv")).unwrap();
+        let results = get_files(files, &paths, backend, &GetOptions::default()).unwrap();
+        assert!(results.iter().all(|r| r.outcome == Outcome::Copied));
+        assert_eq!(fs::read(root.join("first.csv")).unwrap(), b"");
+        assert_eq!(fs::read(root.join("nested/second.csv")).unwrap(), b"");
+    }
+
+    #[test]
+    fn add_files_keep_going_reports_failures_and_tracks_the_rest() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "a.csv", b"a");
+        create_file(&root, "bad.csv", b"bad");
+        create_file(&root, "c.csv", b"c");
+        // A directory where the metadata file should go makes writing it fail
+        fs::create_dir_all(dvs_dir.join("bad.csv.dvs")).unwrap();
+        let files: Vec<PathBuf> = vec!["a.csv".into(), "bad.csv".into(), "c.csv".into()];
+
+        assert!(add_files(files.clone(), &paths, backend, &AddOptions::default()).is_err());
+
+        let options = AddOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let results = add_files(files, &paths, backend, &options).unwrap();
+        assert_eq!(results.len(), 3);
+        let failed: Vec<_> = results.iter().filter(|r| r.error.is_some()).collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path, PathBuf::from("bad.csv"));
+        assert_eq!(failed[0].outcome, None);
+        assert!(dvs_dir.join("a.csv.dvs").is_file());
+        assert!(dvs_dir.join("c.csv.dvs").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_files_runs_the_validator_first() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "good.csv", b"a,b\n1,2\n");
+        create_file(&root, "truncated.csv", b"a,b\n1,");
+        let files: Vec<PathBuf> = vec!["good.csv".into(), "truncated.csv".into()];
+        // Rejects files not ending with a newline
+        let validator =
+            r#"[ -z "$(tail -c 1 "$0")" ] || { echo "no trailing newline" >&2; exit 1; }"#;
+        let mut options = AddOptions {
+            validate_command: Some(vec!["sh".into(), "-c".into(), validator.into()]),
+            ..Default::default()
+        };
+
+        let err = add_files(files.clone(), &paths, backend, &options).unwrap_err();
+        let err = format!("{err:#}");
+        assert!(err.contains("truncated.csv"), "{err}");
+        assert!(err.contains("no trailing newline"), "{err}");
+        // Nothing was added
+        assert!(!dvs_dir.join("good.csv.dvs").exists());
+
+        options.keep_going = true;
+        let results = add_files(files, &paths, backend, &options).unwrap();
+        assert_eq!(results[0].outcome, Some(Outcome::Copied));
+        assert_eq!(results[1].outcome, None);
+        assert!(
+            results[1]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("no trailing newline")
+        );
+        assert!(dvs_dir.join("good.csv.dvs").is_file());
+        assert!(!dvs_dir.join("truncated.csv.dvs").exists());
+    }
+
+    #[test]
+    fn add_files_if_absent_skips_tracked_files() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "tracked.csv", b"v1");
+        add_files(
+            vec!["tracked.csv".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        create_file(&root, "tracked.csv", b"v2");
+        create_file(&root, "new.csv", b"new");
+
+        let options = AddOptions {
+            if_absent: true,
+            ..Default::default()
+        };
+        let results = add_files(
+            vec!["tracked.csv".into(), "new.csv".into()],
+            &paths,
+            backend,
+            &options,
+        )
+        .unwrap();
+
+        let outcomes: Vec<_> = results
+            .iter()
+            .map(|r| (r.path.clone(), r.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (PathBuf::from("tracked.csv"), Some(Outcome::Skipped)),
+                (PathBuf::from("new.csv"), Some(Outcome::Copied)),
+            ]
+        );
+        // The tracked version was not replaced
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        let tracked = statuses
+            .iter()
+            .find(|s| s.path == Path::new("tracked.csv"))
+            .unwrap();
+        assert_eq!(tracked.status, Status::Unsynced);
+    }
+
+    #[test]
+    fn add_files_warns_about_binary_content_in_text_files() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "data.csv", &[0x1f, 0x8b, 0x08, 0x00, 0xff, 0x00]);
+        create_file(&root, "text.csv", b"a,b\n");
+
+        let options = AddOptions {
+            text_extensions: config.text_extensions(),
+            ..Default::default()
+        };
+        let results = add_files(
+            vec!["data.csv".into(), "text.csv".into()],
+            &paths,
+            backend,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(
+            results[0].warnings,
+            vec!["data.csv is expected to be text but looks like gzip compressed data"]
+        );
+        assert_eq!(results[0].outcome, Some(Outcome::Copied));
+        assert!(results[1].warnings.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_files_rejects_named_pipes() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let created = std::process::Command::new("mkfifo")
+            .arg(root.join("pipe.csv"))
+            .status()
+            .unwrap();
+        assert!(created.success());
+        create_file(&root, "data.csv", b"data");
+
+        // Would hang if we tried to hash the pipe
+        let err = add_files(
+            vec!["data.csv".into(), "pipe.csv".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Only regular files can be added, the following are not: pipe.csv (named pipe)"
+        );
+        assert!(!dvs_dir.join("data.csv.dvs").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_files_keep_going_reports_files_failing_the_checks() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let created = std::process::Command::new("mkfifo")
+            .arg(root.join("pipe.csv"))
+            .status()
+            .unwrap();
+        assert!(created.success());
+        create_file(&root, "data.csv", b"data");
+        create_file(&root, "empty.csv", b"");
+        let files: Vec<PathBuf> = vec![
+            "pipe.csv".into(),
+            "missing.csv".into(),
+            "empty.csv".into(),
+            "data.csv".into(),
+        ];
+        let options = AddOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+
+        let results = add_files(files, &paths, backend, &options).unwrap();
+        let errors: Vec<_> = results.iter().map(|r| r.error.as_deref()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                Some("Only regular files can be added, pipe.csv is a named pipe"),
+                Some("missing.csv was not found"),
+                Some("empty.csv is empty, use --allow-empty to add it anyway"),
+                None,
+            ]
+        );
+        assert_eq!(results[3].outcome, Some(Outcome::Copied));
+        assert!(dvs_dir.join("data.csv.dvs").is_file());
+        assert!(!dvs_dir.join("empty.csv.dvs").exists());
+    }
+
+    #[test]
+    fn add_files_records_source_mtime() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let file_path = create_file(&root, "data.csv", b"data");
+        let mtime = fs::metadata(&file_path).unwrap().modified().unwrap();
+        let expected = jiff::Timestamp::try_from(mtime).unwrap().to_string();
+
+        add_files(
+            vec!["data.csv".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        let metadata = FileMetadata::load(paths.metadata_path(Path::new("data.csv"))).unwrap();
+        assert_eq!(metadata.mtime.as_deref(), Some(expected.as_str()));
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert_eq!(statuses[0].mtime.as_deref(), Some(expected.as_str()));
+    }
+
     #[test]
     fn add_files_errors_when_not_found() {
         let (_tmp, root) = create_temp_git_repo();
@@ -639,8 +2242,7 @@ mod tests {
             vec!["nonexistent.csv".into()],
             &paths,
             backend,
-            None,
-            Compression::Zstd,
+            &AddOptions::default(),
         );
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
@@ -658,12 +2260,16 @@ mod tests {
             vec!["a.txt".into()],
             &paths,
             backend,
-            None,
-            Compression::Zstd,
+            &AddOptions::default(),
         )
         .unwrap();
 
-        let result = get_files(vec!["nonexistent.csv".into()], &paths, backend);
+        let result = get_files(
+            vec!["nonexistent.csv".into()],
+            &paths,
+            backend,
+            &GetOptions::default(),
+        );
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
     }
@@ -680,14 +2286,14 @@ mod tests {
 
         // Add files
         let results =
-            add_files(file_paths.clone(), &paths, backend, None, Compression::Zstd).unwrap();
+            add_files(file_paths.clone(), &paths, backend, &AddOptions::default()).unwrap();
         assert_eq!(results.len(), expected_files.len());
         for result in &results {
-            assert_eq!(result.outcome, Outcome::Copied);
+            assert_eq!(result.outcome, Some(Outcome::Copied));
         }
 
         // Verify correct files are tracked
-        let statuses = get_status(&paths).unwrap();
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
         assert_eq!(statuses.len(), expected_files.len());
         let tracked_names: Vec<_> = statuses.iter().map(|s| s.path.to_str().unwrap()).collect();
         for expected in expected_files {
@@ -703,7 +2309,7 @@ mod tests {
         }
 
         // Get files back
-        let results = get_files(file_paths, &paths, backend).unwrap();
+        let results = get_files(file_paths, &paths, backend, &GetOptions::default()).unwrap();
         assert_eq!(results.len(), expected_files.len());
         for result in &results {
             assert_eq!(result.outcome, Outcome::Copied);
@@ -771,10 +2377,242 @@ mod tests {
             "Metadata should be updated to new hash"
         );
 
-        let status = get_file_status(&paths, "b.txt").unwrap();
+        let status = get_file_status(&paths, "b.txt", None).unwrap().status;
         assert_eq!(status, Status::Current);
     }
 
+    #[test]
+    fn verify_files_reports_corrupt_and_missing_objects() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let files = ["ok.txt", "corrupt.txt", "missing.txt"];
+        for name in files {
+            create_file(&root, name, name.as_bytes());
+        }
+        add_files(
+            files.iter().map(PathBuf::from).collect(),
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        for name in files {
+            fs::remove_file(root.join(name)).unwrap();
+        }
+
+        let corrupt = FileMetadata::load(paths.metadata_path(Path::new("corrupt.txt"))).unwrap();
+        let storage_path = root
+            .join(".storage")
+            .join(&corrupt.hashes.blake3[..2])
+            .join(&corrupt.hashes.blake3[2..]);
+        fs::write(&storage_path, b"corrupted content").unwrap();
+        let missing = FileMetadata::load(paths.metadata_path(Path::new("missing.txt"))).unwrap();
+        backend.remove(&missing.hashes).unwrap();
+
+        let results =
+            verify_files(files.iter().map(PathBuf::from).collect(), &paths, backend).unwrap();
+        let outcomes: Vec<_> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                VerifyOutcome::Ok,
+                VerifyOutcome::Corrupt,
+                VerifyOutcome::Missing
+            ]
+        );
+        // Nothing was written to the working tree
+        for name in files {
+            assert!(!root.join(name).exists());
+        }
+    }
+
+    #[test]
+    fn get_files_refuses_to_overwrite_local_changes_unless_asked() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "edited.csv", b"original");
+        create_file(&root, "deleted.csv", b"deleted");
+        let files: Vec<PathBuf> = vec!["edited.csv".into(), "deleted.csv".into()];
+        add_files(files.clone(), &paths, backend, &AddOptions::default()).unwrap();
+        create_file(&root, "edited.csv", b"local changes");
+        fs::remove_file(root.join("deleted.csv")).unwrap();
+
+        let err = get_files(files.clone(), &paths, backend, &GetOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("edited.csv"));
+        assert!(!err.to_string().contains("deleted.csv"));
+        // Nothing was retrieved
+        assert_eq!(fs::read(root.join("edited.csv")).unwrap(), b"local changes");
+        assert!(!root.join("deleted.csv").exists());
+
+        let options = GetOptions {
+            overwrite: true,
+            ..Default::default()
+        };
+        get_files(files, &paths, backend, &options).unwrap();
+        assert_eq!(fs::read(root.join("edited.csv")).unwrap(), b"original");
+        assert_eq!(fs::read(root.join("deleted.csv")).unwrap(), b"deleted");
+    }
+
+    #[test]
+    fn atomic_get_replaces_files_in_place() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        seed_tracked(&root, &config, &[("nested/a.csv", b"a")]);
+        fs::write(root.join("nested/a.csv"), b"edited").unwrap();
+        let options = GetOptions {
+            atomic: true,
+            overwrite: true,
+        };
+
+        let results = get_files(vec!["nested/a.csv".into()], &paths, backend, &options).unwrap();
+        assert_eq!(results[0].outcome, Outcome::Copied);
+        assert_eq!(fs::read(root.join("nested/a.csv")).unwrap(), b"a");
+        // Neither the staged file nor the backup of the edited one are left
+        let names: Vec<_> = fs::read_dir(root.join("nested"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["a.csv"]);
+    }
+
+    #[test]
+    fn atomic_get_leaves_working_tree_unchanged_on_corrupt_object() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        seed_tracked(
+            &root,
+            &config,
+            &[("a.csv", b"a"), ("nested/b.csv", b"b"), ("c.csv", b"c")],
+        );
+        fs::remove_file(root.join("a.csv")).unwrap();
+        fs::remove_dir_all(root.join("nested")).unwrap();
+        fs::remove_file(root.join("c.csv")).unwrap();
+        let c = FileMetadata::load(paths.metadata_path(Path::new("c.csv"))).unwrap();
+        let c_object = config
+            .storage_path()
+            .join(&c.hashes.blake3[..2])
+            .join(&c.hashes.blake3[2..]);
+        fs::write(&c_object, b"corrupted").unwrap();
+        let files: Vec<PathBuf> = vec!["a.csv".into(), "nested/b.csv".into(), "c.csv".into()];
+        let options = GetOptions {
+            atomic: true,
+            ..Default::default()
+        };
+        let gitignore = fs::read_to_string(root.join(".gitignore")).unwrap();
+
+        let err = get_files(files.clone(), &paths, backend, &options).unwrap_err();
+        assert!(format!("{err:#}").contains("c.csv"), "{err:#}");
+        assert!(!root.join("a.csv").exists());
+        assert!(!root.join("nested").exists());
+        assert!(!root.join("c.csv").exists());
+        // Staged files are cleaned up, and the folders created for them
+        let leftovers: Vec<_> = WalkDir::new(&root)
+            .into_iter()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.contains(".dvs-staging-"))
+            .collect();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+        // And nothing was logged as retrieved, seeding doesn't log anything either
+        assert!(backend.read_audit_file(&[]).is_err());
+
+        fs::write(&c_object, b"c").unwrap();
+        create_file(&root, "a.csv", b"a");
+        let results = get_files(files, &paths, backend, &options).unwrap();
+        let outcomes: Vec<_> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::Present, Outcome::Copied, Outcome::Copied]
+        );
+        assert_eq!(fs::read(root.join("nested/b.csv")).unwrap(), b"b");
+        assert_eq!(fs::read(root.join("c.csv")).unwrap(), b"c"); // Getting files leaves the .gitignore alone, init already ignores staged files
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            gitignore
+        );
+    }
+
+    #[test]
+    fn get_files_writes_audit_entries() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        create_file(&root, "audited.txt", b"audited");
+        create_file(&root, "present.txt", b"present");
+        add_files(
+            vec!["audited.txt".into(), "present.txt".into()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+        fs::remove_file(root.join("audited.txt")).unwrap();
+
+        get_files(
+            vec!["audited.txt".into(), "present.txt".into()],
+            &paths,
+            backend,
+            &GetOptions::default(),
+        )
+        .unwrap();
+
+        let entries = backend.read_audit_file(&[]).unwrap();
+        let gets: Vec<_> = entries
+            .iter()
+            .filter(|e| e.action == Action::Get)
+            .map(|e| (e.file.path.clone(), e.outcome))
+            .collect();
+        assert_eq!(
+            gets,
+            vec![
+                (PathBuf::from("audited.txt"), Some(Outcome::Copied)),
+                (PathBuf::from("present.txt"), Some(Outcome::Present)),
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_log_records_forward_slash_paths() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        let native = Path::new("data").join("raw").join("file.csv");
+        create_file(&root, "data/raw/file.csv", b"nested");
+
+        add_files(
+            vec![native.clone()],
+            &paths,
+            backend,
+            &AddOptions::default(),
+        )
+        .unwrap();
+
+        let entries = backend.read_audit_file(&[native]).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file.path, PathBuf::from("data/raw/file.csv"));
+    }
+
+    #[test]
+    fn seeded_files_are_current() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let paths = make_paths(&root, &config);
+        seed_tracked(&root, &config, &[("a.csv", b"a"), ("nested/b.csv", b"b")]);
+
+        let statuses = get_status(&paths, &StatusOptions::default()).unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses.iter().all(|s| s.status == Status::Current));
+    }
+
     #[test]
     fn get_file_errors_on_corrupted_storage() {
         let (_tmp, root) = create_temp_git_repo();
@@ -800,7 +2638,40 @@ mod tests {
         fs::write(&storage_path, b"corrupted content").unwrap();
 
         // get_file should error on decompression or hash mismatch
-        let result = get_file(backend, &paths, "data.txt");
+        let result = get_file(Uuid::new_v4(), backend, &paths, "data.txt", None);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn get_file_reports_bad_objects_as_integrity_errors() {
+        let (_tmp, root) = create_temp_git_repo();
+        let (config, _dvs_dir) = init_dvs_repo(&root);
+        let backend = config.backend();
+        let paths = make_paths(&root, &config);
+        seed_tracked(&root, &config, &[("data.txt", b"original content")]);
+        let metadata = FileMetadata::load(paths.metadata_path(Path::new("data.txt"))).unwrap();
+        let storage_path = root
+            .join(".storage")
+            .join(&metadata.hashes.blake3[..2])
+            .join(&metadata.hashes.blake3[2..]);
+        let is_integrity_error = |result: Result<Outcome>| {
+            result
+                .unwrap_err()
+                .chain()
+                .any(|e| e.is::<IntegrityError>())
+        };
+
+        fs::remove_file(root.join("data.txt")).unwrap();
+        fs::write(&storage_path, b"corrupted content").unwrap();
+        let result = get_file(Uuid::new_v4(), backend, &paths, "data.txt", None);
+        assert!(is_integrity_error(result));
+        assert!(!root.join("data.txt").exists());
+
+        fs::remove_file(&storage_path).unwrap();
+        let result = get_file(Uuid::new_v4(), backend, &paths, "data.txt", None);
+        assert!(is_integrity_error(result));
+
+        let result = get_file(Uuid::new_v4(), backend, &paths, "untracked.txt", None);
+        assert!(!is_integrity_error(result));
+    }
 }
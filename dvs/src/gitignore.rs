@@ -24,48 +24,58 @@ pub(crate) fn add_to_gitignore(repo_root: &Path, paths: &[PathBuf]) -> Result<()
 
     for (dir, dir_paths) in &by_dir {
         let gitignore_path = repo_root.join(dir).join(".gitignore");
+        let entries = dir_paths
+            .iter()
+            .filter_map(|p| Some(format!("/{}", p.file_name()?.to_string_lossy())));
+        append_entries(&gitignore_path, entries)?;
+    }
 
-        let existing = if gitignore_path.is_file() {
-            fs::read_to_string(&gitignore_path)?
-        } else {
-            String::new()
-        };
+    Ok(())
+}
 
-        let existing_lines: HashSet<&str> = existing.lines().collect();
+/// Adds patterns matching in any directory to the root `.gitignore`, eg for temporary files.
+/// If no `.git` folder exists, this is a no-op.
+pub(crate) fn add_patterns_to_gitignore(repo_root: &Path, patterns: &[&str]) -> Result<()> {
+    if !repo_root.join(".git").exists() {
+        return Ok(());
+    }
+    append_entries(
+        &repo_root.join(".gitignore"),
+        patterns.iter().map(|p| p.to_string()),
+    )
+}
 
-        let new_entries: Vec<String> = dir_paths
-            .iter()
-            .filter_map(|p| {
-                let name = p.file_name()?;
-                let entry = format!("/{}", name.to_string_lossy());
-                if existing_lines.contains(entry.as_str()) {
-                    None
-                } else {
-                    Some(entry)
-                }
-            })
-            .collect();
-
-        if new_entries.is_empty() {
-            continue;
-        }
-
-        let mut content = existing;
-        if !content.is_empty() && !content.ends_with('\n') {
-            content.push('\n');
-        }
-        for entry in &new_entries {
-            content.push_str(entry);
-            content.push('\n');
-        }
-
-        // Ensure the directory exists (it may not for nested paths)
-        if let Some(parent) = gitignore_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        fs::write(&gitignore_path, content)?;
+/// Appends the entries missing from the `.gitignore` at `gitignore_path`, creating it if needed
+fn append_entries(gitignore_path: &Path, entries: impl Iterator<Item = String>) -> Result<()> {
+    let existing = if gitignore_path.is_file() {
+        fs::read_to_string(gitignore_path)?
+    } else {
+        String::new()
+    };
+
+    let existing_lines: HashSet<&str> = existing.lines().collect();
+    let new_entries: Vec<String> = entries
+        .filter(|entry| !existing_lines.contains(entry.as_str()))
+        .collect();
+
+    if new_entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for entry in &new_entries {
+        content.push_str(entry);
+        content.push('\n');
     }
 
+    // Ensure the directory exists (it may not for nested paths)
+    if let Some(parent) = gitignore_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(gitignore_path, content)?;
     Ok(())
 }
 
@@ -97,6 +107,16 @@ mod tests {
         assert_eq!(models_content, "/big.bin\n");
     }
 
+    #[test]
+    fn test_add_patterns_to_gitignore() {
+        let (_tmp, root) = create_temp_git_repo();
+        fs::write(root.join(".gitignore"), "/data.csv").unwrap();
+        add_patterns_to_gitignore(&root, &[".*.tmp-*"]).unwrap();
+        add_patterns_to_gitignore(&root, &[".*.tmp-*"]).unwrap();
+        let content = fs::read_to_string(root.join(".gitignore")).unwrap();
+        assert_eq!(content, "/data.csv\n.*.tmp-*\n");
+    }
+
     #[test]
     fn test_no_op_without_git_folder() {
         let tmp = tempfile::tempdir().unwrap();
@@ -1,45 +1,498 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256, Sha512};
 use std::fmt::Display;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+use fs_err as fs;
+use xxhash_rust::xxh3::Xxh3;
+
+/// How much we read at once when hashing a file
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// How files are read when hashing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashOptions {
+    /// How many bytes are read at once, 0 uses the default of 1 MiB
+    pub chunk_size: usize,
+    /// Hash with every algorithm at once, one thread each started per file, blake3 on all cores.
+    /// When only blake3 is needed, the file is memory mapped and hashed on all cores instead.
+    /// Only worth it for large files on fast storage.
+    pub parallel: bool,
+}
+
+impl HashOptions {
+    fn chunk_size(&self) -> usize {
+        match self.chunk_size {
+            0 => READ_BUFFER_SIZE,
+            n => n,
+        }
+    }
+}
+
+impl Default for HashOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: READ_BUFFER_SIZE,
+            parallel: false,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum HashAlg {
     Blake3,
     Md5,
+    Sha256,
+    Sha512,
+    Xxh128,
+}
+
+impl HashAlg {
+    pub const ALL: [HashAlg; 5] = [
+        HashAlg::Blake3,
+        HashAlg::Md5,
+        HashAlg::Sha256,
+        HashAlg::Sha512,
+        HashAlg::Xxh128,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlg::Blake3 => "blake3",
+            HashAlg::Md5 => "md5",
+            HashAlg::Sha256 => "sha256",
+            HashAlg::Sha512 => "sha512",
+            HashAlg::Xxh128 => "xxh128",
+        }
+    }
 }
 
 /// By default, blake3 is used locally but for example AWS/Azure automatically computes
 /// MD5 so it makes sense to use MD5 for those.
-/// We compute both so we can easily switch backends if needed.
+/// sha256 and sha512 are recorded for interop with external systems that only know about
+/// them, xxh128 for fast non-cryptographic checks of huge files.
+/// We compute all of them so we can easily switch backends if needed.
+/// Hashes are lowercased when read so metadata edited by hand or written by other tools
+/// with uppercase hex still compares equal.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Hashes {
+    #[serde(deserialize_with = "lowercase")]
     pub blake3: String,
+    #[serde(deserialize_with = "lowercase")]
     pub md5: String,
+    /// Missing from metadata written by older versions of dvs
+    #[serde(
+        default,
+        deserialize_with = "lowercase_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sha256: Option<String>,
+    /// Missing from metadata written by older versions of dvs
+    #[serde(
+        default,
+        deserialize_with = "lowercase_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sha512: Option<String>,
+    /// Missing from metadata written by older versions of dvs
+    #[serde(
+        default,
+        deserialize_with = "lowercase_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub xxh128: Option<String>,
+}
+
+fn lowercase<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.to_ascii_lowercase())
+}
+
+fn lowercase_opt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.map(|h| h.to_ascii_lowercase()))
 }
 
 impl From<Vec<u8>> for Hashes {
     fn from(bytes: Vec<u8>) -> Self {
-        let blake3_hash = format!("{}", blake3::hash(&bytes));
-        let md5_hash = format!("{:x}", md5::compute(&bytes));
+        let mut hashers = Hashers::new();
+        hashers.update(&bytes);
+        hashers.finalize()
+    }
+}
+
+impl Hashes {
+    /// Hashes everything readable from `reader` without loading it all in memory.
+    /// `on_progress` is called after each chunk with the number of bytes hashed so far.
+    /// Returns the hashes and the total number of bytes read.
+    pub fn from_reader(reader: impl Read, on_progress: impl FnMut(u64)) -> io::Result<(Self, u64)> {
+        let mut hashers = Hashers::new();
+        let total = hash_stream(
+            reader,
+            READ_BUFFER_SIZE,
+            |chunk| hashers.update(chunk),
+            on_progress,
+        )?;
+        Ok((hashers.finalize(), total))
+    }
+
+    /// Same as `from_reader` for the file at `path`, read as described by `options`
+    pub fn from_file(
+        path: &Path,
+        options: &HashOptions,
+        on_progress: impl FnMut(u64),
+    ) -> io::Result<(Self, u64)> {
+        let file = fs::File::open(path)?;
+        if options.parallel {
+            return Hashers::new().hash_parallel(file, options.chunk_size(), on_progress);
+        }
+        let mut hashers = Hashers::new();
+        let total = hash_stream(
+            file,
+            options.chunk_size(),
+            |chunk| hashers.update(chunk),
+            on_progress,
+        )?;
+        Ok((hashers.finalize(), total))
+    }
+
+    /// Whether every algorithm recorded on both sides agrees.
+    /// This lets metadata written before some algorithms were recorded still match a fresh hash.
+    /// Unlike `==` this is not transitive, so only use it to compare content.
+    pub fn matches(&self, other: &Hashes) -> bool {
+        HashAlg::ALL
+            .into_iter()
+            .all(|alg| match (self.get_by_alg(alg), other.get_by_alg(alg)) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            })
+    }
+
+    /// Returns the hash for the given algorithm, if it was recorded.
+    pub fn get_by_alg(&self, alg: HashAlg) -> Option<&str> {
+        match alg {
+            HashAlg::Blake3 => Some(&self.blake3),
+            HashAlg::Md5 => Some(&self.md5),
+            HashAlg::Sha256 => self.sha256.as_deref(),
+            HashAlg::Sha512 => self.sha512.as_deref(),
+            HashAlg::Xxh128 => self.xxh128.as_deref(),
+        }
+    }
 
+    /// All the (algorithm, hash) pairs recorded
+    pub fn recorded(&self) -> impl Iterator<Item = (HashAlg, &str)> {
+        HashAlg::ALL
+            .into_iter()
+            .filter_map(|alg| self.get_by_alg(alg).map(|h| (alg, h)))
+    }
+}
+
+/// Only the blake3 hash of the file at `path` and its size.
+/// Enough to tell whether a file matches its metadata, for a fraction of the cost of
+/// computing every hash. With `options.parallel`, the file is memory mapped and hashed
+/// on all cores, only reporting progress once done.
+pub fn blake3_file(
+    path: &Path,
+    options: &HashOptions,
+    mut on_progress: impl FnMut(u64),
+) -> io::Result<(String, u64)> {
+    let mut hasher = blake3::Hasher::new();
+    if options.parallel {
+        hasher.update_mmap_rayon(path)?;
+        let total = hasher.count();
+        on_progress(total);
+        return Ok((hasher.finalize().to_string(), total));
+    }
+    let total = hash_stream(
+        fs::File::open(path)?,
+        options.chunk_size(),
+        |chunk| {
+            hasher.update(chunk);
+        },
+        on_progress,
+    )?;
+    Ok((hasher.finalize().to_string(), total))
+}
+
+/// Same as `blake3_file` for everything readable from `reader`
+pub fn blake3_reader(reader: impl Read) -> io::Result<(String, u64)> {
+    let mut hasher = blake3::Hasher::new();
+    let total = hash_stream(
+        reader,
+        READ_BUFFER_SIZE,
+        |chunk| {
+            hasher.update(chunk);
+        },
+        |_| {},
+    )?;
+    Ok((hasher.finalize().to_string(), total))
+}
+
+/// Computes every hash we record in a single pass
+struct Hashers {
+    blake3: blake3::Hasher,
+    md5: md5::Context,
+    sha256: Sha256,
+    sha512: Sha512,
+    xxh128: Xxh3,
+}
+
+impl Hashers {
+    fn new() -> Self {
         Self {
-            blake3: blake3_hash,
-            md5: md5_hash,
+            blake3: blake3::Hasher::new(),
+            md5: md5::Context::new(),
+            sha256: Sha256::new(),
+            sha512: Sha512::new(),
+            xxh128: Xxh3::new(),
+        }
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.blake3.update(chunk);
+        self.md5.consume(chunk);
+        self.sha256.update(chunk);
+        self.sha512.update(chunk);
+        self.xxh128.update(chunk);
+    }
+
+    /// Hashes everything readable from `reader` with every algorithm at once: one thread per
+    /// algorithm is started for the whole file and each chunk is sent to all of them, while
+    /// blake3 uses all cores on this thread. The file is then hashed in the time of the slowest
+    /// algorithm instead of the sum of all.
+    fn hash_parallel(
+        self,
+        reader: impl Read,
+        chunk_size: usize,
+        on_progress: impl FnMut(u64),
+    ) -> io::Result<(Hashes, u64)> {
+        let Self {
+            mut blake3,
+            md5,
+            sha256,
+            sha512,
+            xxh128,
+        } = self;
+        std::thread::scope(|scope| {
+            let (md5_sender, md5) = spawn_hasher(scope, md5, |h, chunk| h.consume(chunk));
+            let (sha256_sender, sha256) = spawn_hasher(scope, sha256, |h, chunk| h.update(chunk));
+            let (sha512_sender, sha512) = spawn_hasher(scope, sha512, |h, chunk| h.update(chunk));
+            let (xxh128_sender, xxh128) = spawn_hasher(scope, xxh128, |h, chunk| h.update(chunk));
+            let senders = [md5_sender, sha256_sender, sha512_sender, xxh128_sender];
+            let total = hash_stream(
+                reader,
+                chunk_size,
+                |chunk| {
+                    let chunk: Arc<[u8]> = Arc::from(chunk);
+                    for sender in &senders {
+                        // Only fails if the thread panicked, which joining it reports
+                        let _ = sender.send(Arc::clone(&chunk));
+                    }
+                    blake3.update_rayon(&chunk);
+                },
+                on_progress,
+            );
+            // Lets the threads finish once they hashed what was sent
+            drop(senders);
+            let hashers = Hashers {
+                blake3,
+                md5: md5.join().expect("md5 thread panicked"),
+                sha256: sha256.join().expect("sha256 thread panicked"),
+                sha512: sha512.join().expect("sha512 thread panicked"),
+                xxh128: xxh128.join().expect("xxh128 thread panicked"),
+            };
+            Ok((hashers.finalize(), total?))
+        })
+    }
+
+    fn finalize(self) -> Hashes {
+        Hashes {
+            blake3: self.blake3.finalize().to_string(),
+            md5: format!("{:x}", self.md5.finalize()),
+            sha256: Some(format!("{:x}", self.sha256.finalize())),
+            sha512: Some(format!("{:x}", self.sha512.finalize())),
+            xxh128: Some(format!("{:032x}", self.xxh128.digest128())),
         }
     }
 }
 
-impl Hashes {
-    pub fn get_by_alg(&self, alg: HashAlg) -> &str {
-        match alg {
-            HashAlg::Blake3 => &self.blake3,
-            HashAlg::Md5 => &self.md5,
+/// Starts a thread feeding every chunk sent to it to `hasher`, which it returns once the
+/// sender is dropped
+fn spawn_hasher<'scope, H: Send + 'scope>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    mut hasher: H,
+    update: impl Fn(&mut H, &[u8]) + Send + 'scope,
+) -> (
+    mpsc::SyncSender<Arc<[u8]>>,
+    thread::ScopedJoinHandle<'scope, H>,
+) {
+    // A few chunks can wait so reading isn't held up by the slowest hasher on every chunk
+    let (sender, receiver) = mpsc::sync_channel::<Arc<[u8]>>(4);
+    let handle = scope.spawn(move || {
+        for chunk in receiver {
+            update(&mut hasher, &chunk);
         }
+        hasher
+    });
+    (sender, handle)
+}
+
+/// Reads `reader` by chunks of `chunk_size`, passing each to `update`.
+/// Returns the number of bytes read.
+fn hash_stream(
+    mut reader: impl Read,
+    chunk_size: usize,
+    mut update: impl FnMut(&[u8]),
+    mut on_progress: impl FnMut(u64),
+) -> io::Result<u64> {
+    let mut buffer = vec![0; chunk_size];
+    let mut total = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        update(&buffer[..read]);
+        total += read as u64;
+        on_progress(total);
     }
+
+    Ok(total)
 }
 
 impl Display for Hashes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Hashes(md5={}, blake3={})", self.md5, self.blake3)
+        write!(f, "Hashes(md5={}, blake3={}", self.md5, self.blake3)?;
+        for alg in [HashAlg::Sha256, HashAlg::Sha512, HashAlg::Xxh128] {
+            if let Some(hash) = self.get_by_alg(alg) {
+                write!(f, ", {}={hash}", alg.name())?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_record_sha256() {
+        let hashes = Hashes::from(b"hello world".to_vec());
+        assert_eq!(
+            hashes.get_by_alg(HashAlg::Sha256),
+            Some("b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9")
+        );
+        assert_eq!(hashes.recorded().count(), 5);
+    }
+
+    #[test]
+    fn hashes_record_sha512_and_xxh128() {
+        let hashes = Hashes::from(b"hello world".to_vec());
+        assert_eq!(
+            hashes.get_by_alg(HashAlg::Sha512),
+            Some(
+                "309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f\
+                 989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f"
+            )
+        );
+        let xxh128 = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(b"hello world"));
+        assert_eq!(hashes.get_by_alg(HashAlg::Xxh128), Some(xxh128.as_str()));
+
+        // Written and read back, every algorithm is kept
+        let json = serde_json::to_string(&hashes).unwrap();
+        let read: Hashes = serde_json::from_str(&json).unwrap();
+        for alg in HashAlg::ALL {
+            assert_eq!(read.get_by_alg(alg), hashes.get_by_alg(alg), "{alg:?}");
+        }
+    }
+
+    #[test]
+    fn from_reader_matches_in_memory_hashing() {
+        let content: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 17).map(|i| i as u8).collect();
+        let mut progress = Vec::new();
+        let (hashes, size) =
+            Hashes::from_reader(content.as_slice(), |done| progress.push(done)).unwrap();
+
+        assert_eq!(hashes, Hashes::from(content.clone()));
+        assert_eq!(size, content.len() as u64);
+        assert_eq!(progress.len(), 4);
+        assert_eq!(progress.last(), Some(&size));
+    }
+
+    #[test]
+    fn from_file_matches_whatever_the_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("data.bin");
+        let content: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let expected = Hashes::from(content.clone());
+
+        for parallel in [false, true] {
+            for chunk_size in [0, 4099, 64 * 1024] {
+                let options = HashOptions {
+                    chunk_size,
+                    parallel,
+                };
+                let mut last_progress = 0;
+                let (hashes, size) =
+                    Hashes::from_file(&path, &options, |done| last_progress = done).unwrap();
+                assert_eq!(hashes, expected, "{options:?}");
+                assert_eq!(hashes.recorded().count(), 5);
+                assert_eq!(size, content.len() as u64);
+                assert_eq!(last_progress, size);
+
+                let (blake3, size) = blake3_file(&path, &options, |_| {}).unwrap();
+                assert_eq!(blake3, expected.blake3, "{options:?}");
+                assert_eq!(size, content.len() as u64);
+            }
+        }
+        assert_eq!(
+            blake3_reader(content.as_slice()).unwrap(),
+            (expected.blake3, content.len() as u64)
+        );
+    }
+
+    #[test]
+    fn hashes_without_sha256_still_match_but_are_not_equal() {
+        let fresh = Hashes::from(b"hello world".to_vec());
+        let legacy: Hashes = serde_json::from_str(&format!(
+            r#"{{"blake3":"{}","md5":"{}"}}"#,
+            fresh.blake3, fresh.md5
+        ))
+        .unwrap();
+        assert_eq!(legacy.sha256, None);
+        assert_eq!(legacy.sha512, None);
+        assert!(legacy.matches(&fresh));
+        // `==` stays strict
+        assert_ne!(legacy, fresh);
+
+        let mut other = fresh.clone();
+        other.sha256 = Some("0".repeat(64));
+        assert!(!other.matches(&fresh));
+        // Both match the legacy hashes but not each other
+        assert!(other.matches(&legacy));
+        let mut other = fresh.clone();
+        other.xxh128 = Some("0".repeat(32));
+        assert!(!other.matches(&fresh));
+    }
+
+    #[test]
+    fn uppercase_hashes_are_lowercased_when_read() {
+        let fresh = Hashes::from(b"hello world".to_vec());
+        let json = serde_json::to_string(&fresh).unwrap().to_ascii_uppercase();
+        let json = json
+            .replace("BLAKE3", "blake3")
+            .replace("MD5", "md5")
+            .replace("SHA256", "sha256")
+            .replace("SHA512", "sha512")
+            .replace("XXH128", "xxh128");
+        let read: Hashes = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, fresh);
     }
 }
@@ -1,15 +1,33 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow, bail};
 use fs_err as fs;
 
 use crate::config::Config;
-use crate::paths::find_repo_root;
+use crate::file::STAGING_GITIGNORE_PATTERN;
+use crate::gitignore::add_patterns_to_gitignore;
+use crate::paths::{CONFIG_FILE_NAME, find_repo_root};
+
+/// Canonicalizes the part of the path that exists, so symlinks or `..` don't hide
+/// where a path that isn't created yet will end up
+fn resolve(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = fs::canonicalize(ancestor) {
+            let rest = path.strip_prefix(ancestor).expect("ancestor is a prefix");
+            return canonical.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
 
 /// Starts a new dvs project.
 /// We need a ready to use Config object + the current directory the user is in
 /// The library handles finding where to create the config file and metadata folder
-pub fn init(current_dir: impl AsRef<Path>, config: Config) -> Result<()> {
+///
+/// Unless `force` is set, it refuses to create a repository nested in another dvs
+/// repository or one whose storage is inside the repository itself: both end up with
+/// files tracked, or stored, twice.
+pub fn init(current_dir: impl AsRef<Path>, config: Config, force: bool) -> Result<()> {
     if Config::find(&current_dir).is_some() {
         bail!(
             "Configuration already exists in {}",
@@ -18,6 +36,31 @@ pub fn init(current_dir: impl AsRef<Path>, config: Config) -> Result<()> {
     }
     let repo_root =
         find_repo_root(&current_dir).ok_or_else(|| anyhow!("Cannot find repository root"))?;
+
+    if !force {
+        let resolved_root = resolve(&repo_root);
+        if let Some(parent_repo) = resolved_root
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join(CONFIG_FILE_NAME).is_file())
+        {
+            bail!(
+                "{} is inside the dvs repository at {}, use --force to create a nested one anyway",
+                repo_root.display(),
+                parent_repo.display()
+            );
+        }
+        let storage = resolve(&current_dir.as_ref().join(config.storage_path()));
+        if storage.starts_with(&resolved_root) {
+            bail!(
+                "The storage directory {} is inside the repository at {}, \
+                 use --force to use it anyway",
+                storage.display(),
+                repo_root.display()
+            );
+        }
+    }
+
     config.save(&repo_root)?;
     log::debug!(
         "Creating metadata folder: {}",
@@ -26,6 +69,8 @@ pub fn init(current_dir: impl AsRef<Path>, config: Config) -> Result<()> {
     fs::create_dir(repo_root.join(config.metadata_folder_name()))?;
     log::debug!("Initializing backend");
     config.backend().init()?;
+    // In case a crash leaves files staged by an atomic get behind
+    add_patterns_to_gitignore(&repo_root, &[STAGING_GITIGNORE_PATTERN])?;
     log::info!("DVS repository initialized successfully");
     Ok(())
 }
@@ -38,10 +83,11 @@ mod tests {
     #[test]
     fn init_creates_config_and_directories() {
         let (_tmp, root) = create_temp_git_repo();
-        let storage = root.join(".storage");
+        let storage_tmp = tempfile::tempdir().unwrap();
+        let storage = storage_tmp.path().join("storage");
 
         let config = Config::new_local(&storage, None, None).unwrap();
-        init(&root, config).unwrap();
+        init(&root, config, false).unwrap();
 
         // Config file should exist
         assert!(root.join("dvs.toml").is_file());
@@ -49,18 +95,22 @@ mod tests {
         assert!(root.join(".dvs").is_dir());
         // Storage folder should exist
         assert!(storage.is_dir());
+        // Files left behind by an interrupted atomic get are ignored
+        let gitignore = fs::read_to_string(root.join(".gitignore")).unwrap();
+        assert_eq!(gitignore, format!("{STAGING_GITIGNORE_PATTERN}\n"));
     }
 
     #[test]
     fn init_fails_if_already_initialized() {
         let (_tmp, root) = create_temp_git_repo();
-        let storage = root.join(".storage");
+        let storage_tmp = tempfile::tempdir().unwrap();
+        let storage = storage_tmp.path().join("storage");
 
         let config = Config::new_local(&storage, None, None).unwrap();
-        init(&root, config.clone()).unwrap();
+        init(&root, config.clone(), false).unwrap();
 
         // Second init should fail
-        let result = init(&root, config);
+        let result = init(&root, config, false);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("already exists"));
     }
@@ -71,7 +121,7 @@ mod tests {
         let storage = tmp.path().join(".storage");
 
         let config = Config::new_local(&storage, None, None).unwrap();
-        let result = init(tmp.path(), config);
+        let result = init(tmp.path(), config, false);
 
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("repository root"));
@@ -82,13 +132,48 @@ mod tests {
         let (_tmp, root) = create_temp_git_repo();
         let subdir = root.join("nested/deep");
         fs::create_dir_all(&subdir).unwrap();
-        let storage = root.join(".storage");
+        let storage_tmp = tempfile::tempdir().unwrap();
+        let storage = storage_tmp.path().join("storage");
 
         let config = Config::new_local(&storage, None, None).unwrap();
-        init(&subdir, config).unwrap();
+        init(&subdir, config, false).unwrap();
 
         // Config should be at repo root, not in subdirectory
         assert!(root.join("dvs.toml").is_file());
         assert!(!subdir.join("dvs.toml").exists());
     }
+
+    #[test]
+    fn init_refuses_nested_repository_unless_forced() {
+        let (_tmp, root) = create_temp_git_repo();
+        let storage_tmp = tempfile::tempdir().unwrap();
+        let config = Config::new_local(storage_tmp.path().join("outer"), None, None).unwrap();
+        init(&root, config, false).unwrap();
+        let nested = root.join("nested");
+        fs::create_dir_all(nested.join(".git")).unwrap();
+
+        let config = Config::new_local(storage_tmp.path().join("inner"), None, None).unwrap();
+        let err = init(&nested, config.clone(), false).unwrap_err();
+        assert!(err.to_string().contains("is inside the dvs repository"));
+        assert!(!nested.join("dvs.toml").exists());
+
+        init(&nested, config, true).unwrap();
+        assert!(nested.join("dvs.toml").is_file());
+    }
+
+    #[test]
+    fn init_refuses_storage_inside_repository_unless_forced() {
+        let (_tmp, root) = create_temp_git_repo();
+        // Relative to the current directory, through `..`
+        let subdir = root.join("sub");
+        fs::create_dir_all(&subdir).unwrap();
+        let config = Config::new_local("../.storage", None, None).unwrap();
+
+        let err = init(&subdir, config.clone(), false).unwrap_err();
+        assert!(err.to_string().contains("is inside the repository"));
+        assert!(!root.join("dvs.toml").exists());
+
+        init(&subdir, config, true).unwrap();
+        assert!(root.join("dvs.toml").is_file());
+    }
 }
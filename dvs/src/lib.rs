@@ -1,22 +1,30 @@
 pub mod audit;
 pub mod backends;
 pub mod config;
+#[cfg(feature = "encryption")]
+mod encryption;
 pub mod file;
 mod gitignore;
 mod hashes;
 pub mod init;
 pub mod paths;
+mod sniff;
 
 pub use backends::Backend;
 pub use config::Compression;
-pub use file::{AddResult, FileMetadata, FileStatus, GetResult, Outcome, Status};
-pub use file::{add_files, get_files, get_status};
-pub use hashes::{HashAlg, Hashes};
+pub use file::{
+    AddOptions, AddResult, FileMetadata, FileStatus, GetOptions, GetResult, HashProgress,
+    IntegrityError, Outcome, Status, StatusOptions, StatusTotals,
+};
+pub use file::{VerifyOutcome, VerifyResult};
+pub use file::{add_files, find_case_collisions, get_files, get_status, verify_files};
+pub use hashes::{HashAlg, HashOptions, Hashes};
 pub use paths::{DvsPaths, find_repo_root};
 
 #[cfg(test)]
 pub mod testutil {
-    use crate::config::Config;
+    use crate::config::{Compression, Config};
+    use crate::file::FileMetadata;
     use crate::init::init;
     use fs_err as fs;
     use std::path::{Path, PathBuf};
@@ -52,8 +60,32 @@ pub mod testutil {
     pub fn init_dvs_repo(repo_root: &Path) -> (Config, PathBuf) {
         let storage_dir = repo_root.join(".storage");
         let config = Config::new_local(&storage_dir, None, None).unwrap();
-        init(repo_root, config.clone()).unwrap();
+        // Storage inside the repo keeps everything in a single temp dir
+        init(repo_root, config.clone(), true).unwrap();
         let dvs_dir = repo_root.join(".dvs");
         (config, dvs_dir)
     }
+
+    /// Puts the given `(relative_path, content)` files in the state they would be after
+    /// being added: working file, stored object and metadata, without going through
+    /// `add_files`. Lets tests start from a given baseline independently of add.
+    /// Objects are stored uncompressed so tests can tamper with them directly.
+    pub fn seed_tracked(repo_root: &Path, config: &Config, files: &[(&str, &[u8])]) {
+        let dvs_dir = repo_root.join(config.metadata_folder_name());
+        for (relative_path, content) in files {
+            let path = create_file(repo_root, relative_path, content);
+            let metadata = FileMetadata::from_file(&path, Compression::None, None).unwrap();
+            config
+                .backend()
+                .store(&metadata.hashes, &path, Compression::None, None)
+                .unwrap();
+            let metadata_path = dvs_dir.join(format!("{relative_path}.dvs"));
+            fs::create_dir_all(metadata_path.parent().unwrap()).unwrap();
+            fs::write(
+                metadata_path,
+                serde_json::to_string_pretty(&metadata).unwrap(),
+            )
+            .unwrap();
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
 
 use crate::config::Config;
 use anyhow::{Result, anyhow};
@@ -26,6 +26,44 @@ pub fn find_repo_root(start_dir: impl AsRef<Path>) -> Option<PathBuf> {
     }
 }
 
+/// Repo-relative paths recorded outside of the working tree, like in the audit log,
+/// always use `/` so they are the same whatever OS wrote them.
+pub fn to_portable_path(path: &Path) -> PathBuf {
+    PathBuf::from(replace_separator(&path.to_string_lossy(), MAIN_SEPARATOR))
+}
+
+fn replace_separator(path: &str, separator: char) -> String {
+    if separator == '/' {
+        path.to_string()
+    } else {
+        path.replace(separator, "/")
+    }
+}
+
+/// Describes what an existing path is if it's neither a regular file nor a directory.
+/// Those can't be tracked: reading a named pipe can hang forever and devices
+/// or sockets don't hold data we could store.
+pub fn special_file_kind(path: &Path) -> Option<&'static str> {
+    let file_type = fs::metadata(path).ok()?.file_type();
+    if file_type.is_file() || file_type.is_dir() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return Some("named pipe");
+        }
+        if file_type.is_socket() {
+            return Some("socket");
+        }
+        if file_type.is_block_device() || file_type.is_char_device() {
+            return Some("device");
+        }
+    }
+    Some("special file")
+}
+
 /// We always need to figure out where the user is in a project,
 /// where the root is etc.
 /// This struct handles all of it so the rest of the code doesn't have to
@@ -141,6 +179,41 @@ mod tests {
         assert_eq!(find_repo_root(&subdir), Some(root));
     }
 
+    #[test]
+    fn portable_paths_use_forward_slashes() {
+        assert_eq!(
+            replace_separator(r"data\raw\file.csv", '\\'),
+            "data/raw/file.csv"
+        );
+        assert_eq!(
+            replace_separator("data/raw/file.csv", '/'),
+            "data/raw/file.csv"
+        );
+        assert_eq!(
+            to_portable_path(&Path::new("data").join("raw").join("file.csv")),
+            PathBuf::from("data/raw/file.csv")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_file_kind_detects_named_pipes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let fifo = tmp.path().join("pipe");
+        let created = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(created.success());
+        fs::write(tmp.path().join("file"), b"").unwrap();
+
+        assert_eq!(special_file_kind(&fifo), Some("named pipe"));
+        assert_eq!(special_file_kind(&tmp.path().join("file")), None);
+        assert_eq!(special_file_kind(tmp.path()), None);
+        assert_eq!(special_file_kind(&tmp.path().join("missing")), None);
+        assert_eq!(special_file_kind(Path::new("/dev/null")), Some("device"));
+    }
+
     #[test]
     fn find_repo_root_returns_none_without_git() {
         let tmp = tempfile::tempdir().unwrap();
@@ -0,0 +1,99 @@
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Result;
+use fs_err as fs;
+
+/// Extensions of files expected to contain text, used when the config doesn't set any
+pub const DEFAULT_TEXT_EXTENSIONS: &[&str] = &[
+    "csv", "tsv", "txt", "json", "jsonl", "yaml", "yml", "xml", "md", "r", "sql",
+];
+
+/// How much of the start of a file we look at
+const SNIFF_SIZE: usize = 8 * 1024;
+
+/// Known binary formats a text file can end up in when a pipeline step
+/// compressed it unexpectedly
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (&[0x1f, 0x8b], "gzip compressed data"),
+    (&[0x28, 0xb5, 0x2f, 0xfd], "zstd compressed data"),
+    (b"PK\x03\x04", "a zip archive"),
+    (b"BZh", "bzip2 compressed data"),
+    (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], "xz compressed data"),
+    (b"PAR1", "a parquet file"),
+];
+
+/// Describes what the content looks like if it's not text
+fn describe_binary(start: &[u8]) -> Option<&'static str> {
+    for (magic, description) in MAGIC_NUMBERS {
+        if start.starts_with(magic) {
+            return Some(description);
+        }
+    }
+    start.contains(&0).then_some("binary data")
+}
+
+/// If a file with one of `text_extensions` looks like binary content, eg a gzip file
+/// named `.csv`, returns what it looks like. Extensions are compared case-insensitively.
+pub fn sniff_binary_text_file(
+    path: &Path,
+    text_extensions: &[String],
+) -> Result<Option<&'static str>> {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return Ok(None);
+    };
+    if !text_extensions
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(extension))
+    {
+        return Ok(None);
+    }
+
+    let mut start = Vec::with_capacity(SNIFF_SIZE);
+    fs::File::open(path)?
+        .take(SNIFF_SIZE as u64)
+        .read_to_end(&mut start)?;
+    Ok(describe_binary(&start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::create_file;
+
+    fn text_extensions() -> Vec<String> {
+        DEFAULT_TEXT_EXTENSIONS
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn gzip_named_csv_is_flagged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = create_file(tmp.path(), "data.CSV", &[0x1f, 0x8b, 0x08, 0x00, 1, 2, 3]);
+        assert_eq!(
+            sniff_binary_text_file(&path, &text_extensions()).unwrap(),
+            Some("gzip compressed data")
+        );
+    }
+
+    #[test]
+    fn text_and_unchecked_extensions_are_not_flagged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let csv = create_file(tmp.path(), "data.csv", b"a,b\n1,2\n");
+        assert_eq!(
+            sniff_binary_text_file(&csv, &text_extensions()).unwrap(),
+            None
+        );
+
+        let gz = create_file(tmp.path(), "data.csv.gz", &[0x1f, 0x8b, 0x08, 0x00]);
+        assert_eq!(
+            sniff_binary_text_file(&gz, &text_extensions()).unwrap(),
+            None
+        );
+        // An empty list disables the check
+        let csv_gz = create_file(tmp.path(), "other.csv", &[0x1f, 0x8b, 0x08, 0x00]);
+        assert_eq!(sniff_binary_text_file(&csv_gz, &[]).unwrap(), None);
+    }
+}